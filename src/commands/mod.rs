@@ -4,7 +4,8 @@ mod moderation;
 mod mtg_spoilers;
 mod quotes;
 pub mod sfx;
-mod tts;
+pub mod tts;
+mod voice;
 
 type Context<'c> = poise::Context<'c, mappable_rc::Marc<super::Bot>, anyhow::Error>;
 type Command = poise::Command<mappable_rc::Marc<super::Bot>, anyhow::Error>;
@@ -23,6 +24,7 @@ pub mod command_groups {
     pub use quotes::commands as quotes;
     pub use sfx::sfx;
     pub use tts::tts;
+    pub use voice::voice;
 
     pub fn all() -> impl Iterator<Item = Command> {
         global()
@@ -32,5 +34,6 @@ pub mod command_groups {
             .chain(quotes())
             .chain([sfx()])
             .chain([tts()])
+            .chain([voice()])
     }
 }
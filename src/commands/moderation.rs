@@ -1,13 +1,20 @@
 use futures::StreamExt;
 use serenity::{
-    all::{MessageId, RoleId},
+    all::{ChannelId, MessageId, RoleId},
     model::prelude::ReactionType,
 };
 
 use poise::{CreateReply, ReplyHandle, command};
 
+use crate::features::moderation::mod_log;
+
 pub fn commands() -> impl Iterator<Item = super::Command> {
-    [add_reaction_role(), add_role_to_all_members()].into_iter()
+    [
+        add_reaction_role(),
+        add_role_to_all_members(),
+        set_mod_log_channel(),
+    ]
+    .into_iter()
 }
 
 /// Add a new role to be react added.
@@ -34,6 +41,21 @@ async fn add_reaction_role(
     Ok(())
 }
 
+/// Set the channel moderation events are logged to. Leave it empty to stop logging.
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn set_mod_log_channel(
+    ctx: super::Context<'_>,
+    channel: Option<ChannelId>,
+) -> anyhow::Result<()> {
+    let gid = ctx.guild_id().expect("should be used in a guild");
+    ctx.say(mod_log::set_channel(gid, channel).await?).await?;
+    Ok(())
+}
+
 /// Add [role] to all users.
 #[command(
     slash_command,
@@ -1,21 +1,39 @@
-use poise::command;
+use std::borrow::Cow;
+
+use anyhow::Context as _;
+use chrono::NaiveTime;
+use itertools::Itertools;
+use poise::{CreateReply, command};
+use serenity::all::{AutocompleteChoice, ChannelId, CreateEmbed, Mentionable as _};
+
+use super::sfx::util::paginate;
+use crate::{
+    features::quotes::vote_button,
+    prefs::{self, guild::QuoteOfTheDay},
+};
 
 pub fn commands() -> impl Iterator<Item = super::Command> {
-    [quote(), quote_add()].into_iter()
+    [
+        quote(),
+        quote_add(),
+        quote_search(),
+        quote_show(),
+        quote_top(),
+        quote_daily(),
+    ]
+    .into_iter()
 }
 
 /// Quote briliant minds
 #[command(slash_command, guild_only)]
 async fn quote(ctx: super::Context<'_>) -> anyhow::Result<()> {
-    ctx.say(
-        ctx.data()
-            .quotes
-            .lock()
-            .await
-            .choose()
-            .unwrap_or("No quotes found!"),
-    )
-    .await?;
+    let reply = ctx.data().quotes.lock().await.choose().map(|(id, quote)| {
+        CreateReply::default()
+            .content(&quote.text)
+            .components(vec![vote_button(id, quote)])
+    });
+    ctx.send(reply.unwrap_or_else(|| CreateReply::default().content("No quotes found!")))
+        .await?;
     Ok(())
 }
 
@@ -26,3 +44,166 @@ async fn quote_add(ctx: super::Context<'_>, quote: String) -> anyhow::Result<()>
     ctx.say("Quote added").await?;
     Ok(())
 }
+
+/// Search for quotes containing some text
+#[command(slash_command, guild_only)]
+async fn quote_search(ctx: super::Context<'_>, text: String) -> anyhow::Result<()> {
+    let pages = {
+        let quotes = ctx.data().quotes.lock().await;
+        let results = quotes.search(&text);
+        let total = results.len();
+        results
+            .into_iter()
+            .chunks(10)
+            .into_iter()
+            .map(|page| {
+                CreateEmbed::new()
+                    .title(format!("Quotes matching {text:?} ({total})"))
+                    .description(
+                        page.format_with("\n", |(id, quote), f| {
+                            f(&format_args!("**#{id}** {}", truncate(quote, 300)))
+                        })
+                        .to_string(),
+                    )
+            })
+            .collect::<Vec<_>>()
+    };
+    if pages.is_empty() {
+        ctx.say(format!("No quotes matching {text:?}")).await?;
+    } else {
+        paginate(ctx, &pages).await?;
+    }
+    Ok(())
+}
+
+/// Show a quote by id
+#[command(slash_command, guild_only)]
+async fn quote_show(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_quote_id"] id: usize,
+) -> anyhow::Result<()> {
+    let reply = ctx.data().quotes.lock().await.get(id).map(|quote| {
+        CreateReply::default()
+            .content(&quote.text)
+            .components(vec![vote_button(id, quote)])
+    });
+    match reply {
+        Some(reply) => ctx.send(reply).await?,
+        None => ctx.say(format!("No quote with id #{id}")).await?,
+    };
+    Ok(())
+}
+
+/// Show the most voted quotes
+#[command(slash_command, guild_only)]
+async fn quote_top(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let pages = {
+        let quotes = ctx.data().quotes.lock().await;
+        quotes
+            .top()
+            .into_iter()
+            .take_while(|(_, q)| q.score() > 0)
+            .chunks(10)
+            .into_iter()
+            .map(|page| {
+                CreateEmbed::new().title("Top quotes").description(
+                    page.format_with("\n", |(id, quote), f| {
+                        f(&format_args!(
+                            "**{}** 👍 **#{id}** {}",
+                            quote.score(),
+                            truncate(&quote.text, 300)
+                        ))
+                    })
+                    .to_string(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    if pages.is_empty() {
+        ctx.say("No quotes have been voted on yet").await?;
+    } else {
+        paginate(ctx, &pages).await?;
+    }
+    Ok(())
+}
+
+/// Post a quote every day in a channel. Leave the channel empty to stop.
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn quote_daily(
+    ctx: super::Context<'_>,
+    #[description = "Channel to post the quote of the day in"] channel: Option<ChannelId>,
+    #[description = "Time of day to post it at (HH:MM)"] time: Option<String>,
+    #[description = "Hours offset from UTC of the time of day"] utc_offset: Option<i8>,
+) -> anyhow::Result<()> {
+    let gid = ctx.guild_id().context("must be in a guild")?;
+    let Some(channel) = channel else {
+        prefs::guild::update(gid, |p| p.quote_of_the_day = None).await?;
+        ctx.say("Quote of the day disabled").await?;
+        return Ok(());
+    };
+    let time = match time {
+        Some(time) => NaiveTime::parse_from_str(&time, "%H:%M")
+            .with_context(|| format!("invalid time {time:?}, expected HH:MM"))?,
+        None => NaiveTime::from_hms_opt(9, 0, 0).expect("9 o'clock exists"),
+    };
+    let utc_offset = match utc_offset {
+        Some(off) => off,
+        None => prefs::user::get(ctx.author().id)
+            .await?
+            .and_then(|p| p.timezone_offset)
+            .unwrap_or_default(),
+    };
+    anyhow::ensure!(
+        (-12..=14).contains(&utc_offset),
+        "invalid UTC offset {utc_offset}, expected -12 to 14"
+    );
+    prefs::guild::update(gid, |p| {
+        p.quote_of_the_day = Some(QuoteOfTheDay {
+            channel,
+            time,
+            utc_offset,
+        })
+    })
+    .await?;
+    ctx.say(format!(
+        "A quote will be posted in {} every day at {} (UTC{utc_offset:+})",
+        channel.mention(),
+        time.format("%H:%M"),
+    ))
+    .await?;
+    Ok(())
+}
+
+async fn autocomplete_quote_id(
+    ctx: super::Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let quotes = ctx.data().quotes.lock().await;
+    let matches = if partial.is_empty() {
+        quotes.iter().collect::<Vec<_>>()
+    } else if partial.chars().all(|c| c.is_ascii_digit()) {
+        quotes
+            .iter()
+            .filter(|(id, _)| id.to_string().starts_with(partial))
+            .collect()
+    } else {
+        quotes.search(partial)
+    };
+    matches
+        .into_iter()
+        .take(25)
+        .map(|(id, quote)| AutocompleteChoice::new(format!("#{id}: {}", truncate(quote, 80)), id))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn truncate(s: &str, max_chars: usize) -> Cow<'_, str> {
+    match s.char_indices().nth(max_chars) {
+        Some((i, _)) => Cow::Owned(format!("{}…", &s[..i])),
+        None => Cow::Borrowed(s),
+    }
+}
@@ -0,0 +1,272 @@
+use anyhow::Context as _;
+use songbird::input::{
+    codecs::{CODEC_REGISTRY, PROBE},
+    core::{
+        audio::{SampleBuffer, SignalSpec},
+        codecs::{CODEC_TYPE_NULL, DecoderOptions},
+        errors::Error as SymphoniaError,
+        formats::FormatOptions,
+        io::MediaSourceStream,
+        meta::MetadataOptions,
+        probe::Hint,
+    },
+};
+use std::{io, time::Duration};
+
+/// Loudness, in dBFS, that clips are normalised to.
+const TARGET_LOUDNESS: f32 = -20.0;
+const MAX_GAIN: f32 = 4.0;
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub codec: &'static str,
+    pub duration: Duration,
+    /// Root mean square of the samples, in dBFS.
+    pub loudness: f32,
+    /// Highest absolute sample value.
+    pub peak: f32,
+}
+
+impl Analysis {
+    /// The volume that brings this clip to the target loudness, without letting it clip.
+    pub fn normalising_gain(&self) -> f32 {
+        let gain = 10f32.powf((TARGET_LOUDNESS - self.loudness) / 20.0);
+        let headroom = if self.peak > 0.0 {
+            1.0 / self.peak
+        } else {
+            MAX_GAIN
+        };
+        gain.min(headroom).clamp(0.0, MAX_GAIN)
+    }
+}
+
+/// Decodes the whole clip into `on_samples`, returning the codec's name.
+fn decode<F>(
+    bytes: Vec<u8>,
+    extension: Option<&str>,
+    mut on_samples: F,
+) -> anyhow::Result<&'static str>
+where
+    F: FnMut(&[f32], SignalSpec),
+{
+    let mut hint = Hint::new();
+    if let Some(ext) = extension {
+        hint.with_extension(ext);
+    }
+    let mss = MediaSourceStream::new(Box::new(io::Cursor::new(bytes)), Default::default());
+    let mut format = PROBE
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .context("not a supported audio format")?
+        .format;
+    let track = format
+        .default_track()
+        .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .context("file has no audio")?;
+    let track_id = track.id;
+    let codec = CODEC_REGISTRY
+        .get_codec(track.codec_params.codec)
+        .map(|c| c.short_name)
+        .context("unsupported audio codec")?;
+    let mut decoder = CODEC_REGISTRY
+        .make(&track.codec_params, &DecoderOptions::default())
+        .context("unsupported audio codec")?;
+
+    let mut samples = None::<SampleBuffer<f32>>;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("failed to read audio"),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        match decoder.decode(&packet) {
+            Ok(buf) => {
+                let spec = *buf.spec();
+                let needed = buf.capacity() * spec.channels.count();
+                if samples.as_ref().is_none_or(|s| s.capacity() < needed) {
+                    samples = Some(SampleBuffer::new(buf.capacity() as u64, spec));
+                }
+                let interleaved = samples.as_mut().expect("was just initialized");
+                interleaved.copy_interleaved_ref(buf);
+                on_samples(interleaved.samples(), spec);
+            }
+            Err(SymphoniaError::DecodeError(e)) => {
+                tracing::debug!("skipping undecodable packet: {e}");
+            }
+            Err(e) => return Err(e).context("failed to decode audio"),
+        }
+    }
+    Ok(codec)
+}
+
+pub fn analyse(bytes: Vec<u8>, extension: Option<&str>) -> anyhow::Result<Analysis> {
+    let mut seconds = 0f64;
+    let mut sum_of_squares = 0f64;
+    let mut n_samples = 0u64;
+    let mut peak = 0f32;
+    let codec = decode(bytes, extension, |samples, spec| {
+        let channels = spec.channels.count();
+        seconds += (samples.len() / channels) as f64 / f64::from(spec.rate);
+        n_samples += samples.len() as u64;
+        for &s in samples {
+            sum_of_squares += f64::from(s) * f64::from(s);
+            peak = peak.max(s.abs());
+        }
+    })?;
+    if n_samples == 0 {
+        anyhow::bail!("no audio decoded");
+    }
+    let mean_square = sum_of_squares / n_samples as f64;
+    Ok(Analysis {
+        codec,
+        duration: Duration::from_secs_f64(seconds),
+        loudness: (10.0 * mean_square.max(f64::MIN_POSITIVE).log10()) as f32,
+        peak,
+    })
+}
+
+/// Which part of a clip to keep, and how to fade it in and out.
+#[derive(Debug, Clone, Default)]
+pub struct Cut {
+    pub start: Duration,
+    pub end: Option<Duration>,
+    pub fade_in: Duration,
+    pub fade_out: Duration,
+}
+
+/// Cuts a clip, re-encoding it as 16-bit PCM wav so every cut clip ends up in the same format.
+pub fn cut(bytes: Vec<u8>, extension: Option<&str>, cut: &Cut) -> anyhow::Result<Vec<u8>> {
+    let mut samples = Vec::new();
+    let mut format = None::<(usize, u32)>;
+    let mut consistent = true;
+    decode(bytes, extension, |chunk, spec| {
+        let spec = (spec.channels.count(), spec.rate);
+        if *format.get_or_insert(spec) == spec {
+            samples.extend_from_slice(chunk);
+        } else {
+            consistent = false;
+        }
+    })?;
+    anyhow::ensure!(
+        consistent,
+        "the clip changes sample rate or channels midway"
+    );
+    let (channels, rate) = format.context("no audio decoded")?;
+    let frames = samples.len() / channels;
+    let to_frames = |d: Duration| ((d.as_secs_f64() * f64::from(rate)) as usize).min(frames);
+
+    let start = to_frames(cut.start);
+    let end = cut.end.map_or(frames, to_frames);
+    anyhow::ensure!(start < end, "the end of the clip must be after its start");
+    let len = end - start;
+    let fade_in = to_frames(cut.fade_in).min(len);
+    let fade_out = to_frames(cut.fade_out).min(len);
+
+    let mut clip = samples[start * channels..end * channels].to_vec();
+    for (i, frame) in clip.chunks_mut(channels).enumerate() {
+        let mut gain = 1.0;
+        if i < fade_in {
+            gain *= i as f32 / fade_in as f32;
+        }
+        if i >= len - fade_out {
+            gain *= (len - i) as f32 / fade_out as f32;
+        }
+        frame.iter_mut().for_each(|s| *s *= gain);
+    }
+    Ok(encode_wav(&clip, channels as u16, rate))
+}
+
+fn encode_wav(samples: &[f32], channels: u16, rate: u32) -> Vec<u8> {
+    const BYTES_PER_SAMPLE: u16 = 2;
+    let data_len = (samples.len() * usize::from(BYTES_PER_SAMPLE)) as u32;
+    let block_align = channels * BYTES_PER_SAMPLE;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&rate.to_le_bytes());
+    wav.extend_from_slice(&(rate * u32::from(block_align)).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&(BYTES_PER_SAMPLE * 8).to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        wav.extend_from_slice(&((s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16).to_le_bytes());
+    }
+    wav
+}
+
+/// Parses times like `12`, `1.5`, `1:02` or `1:02.25`.
+pub fn parse_timestamp(s: &str) -> Option<Duration> {
+    let mut seconds = 0f64;
+    for part in s.trim().split(':') {
+        let part = part
+            .parse::<f64>()
+            .ok()
+            .filter(|p| p.is_finite() && *p >= 0.0)?;
+        seconds = seconds * 60.0 + part;
+    }
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cut, analyse, cut, encode_wav, parse_timestamp};
+    use std::time::Duration;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_timestamp("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_timestamp("1:02"), Some(Duration::from_secs(62)));
+        assert_eq!(
+            parse_timestamp(" 1:02.25 "),
+            Some(Duration::from_millis(62250))
+        );
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("-1"), None);
+        assert_eq!(parse_timestamp("1:x"), None);
+    }
+
+    #[test]
+    fn cuts_and_fades() {
+        let rate = 8000;
+        let wav = encode_wav(&vec![0.5; rate as usize * 4], 1, rate);
+        let clip = cut(
+            wav,
+            Some("wav"),
+            &Cut {
+                start: Duration::from_secs(1),
+                end: Some(Duration::from_secs(3)),
+                fade_in: Duration::from_millis(500),
+                fade_out: Duration::from_millis(500),
+            },
+        )
+        .unwrap();
+        let analysis = analyse(clip, Some("wav")).unwrap();
+        assert_eq!(analysis.duration, Duration::from_secs(2));
+        assert!((analysis.peak - 0.5).abs() < 0.001);
+        // the fades make it quieter than the constant 0.5 it was cut from
+        assert!(analysis.loudness < 20.0 * 0.5f32.log10());
+    }
+
+    #[test]
+    fn rejects_empty_cuts() {
+        let wav = encode_wav(&[0.5; 8000], 1, 8000);
+        let cut_past_the_end = Cut {
+            start: Duration::from_secs(2),
+            ..Default::default()
+        };
+        assert!(cut(wav, Some("wav"), &cut_past_the_end).is_err());
+    }
+}
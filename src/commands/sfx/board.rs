@@ -0,0 +1,228 @@
+use super::{
+    library::Library,
+    permissions,
+    random::{self, Weighting},
+    util,
+};
+use anyhow::Context as _;
+use futures::FutureExt as _;
+use poise::{ChoiceParameter as _, CreateReply, command};
+use pubsub::events;
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, Context, CreateActionRow, CreateButton, GuildId, Interaction,
+};
+use sha1::{Digest as _, Sha1};
+use std::{
+    ffi::OsStr,
+    io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+const PLAY_BUTTON: &str = "sfx-play:";
+const PAGE_BUTTON: &str = "sfx-board:";
+const SHUFFLE_BUTTON: &str = "sfx-shuffle:";
+const MAX_CUSTOM_ID_LEN: usize = 100;
+const MAX_LABEL_LEN: usize = 80;
+
+/// Post a soundboard with a button for every sfx
+#[command(slash_command, guild_only)]
+pub(super) async fn board(
+    ctx: super::super::Context<'_>,
+    #[description = "Only show sfx with this tag"] tag: Option<String>,
+    #[description = "Add a button playing a random sfx"] shuffle: Option<Weighting>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let tag = tag.map(|t| super::normalize_label(&t));
+    let (content, components) = page(guild_id, 0, tag.as_deref(), shuffle).await?;
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components),
+    )
+    .await?;
+    Ok(())
+}
+
+/// File names can be too long for a button id, so buttons name clips by a digest instead.
+fn clip_id(file_name: &OsStr) -> String {
+    format!("{:x}", Sha1::digest(file_name.as_encoded_bytes()))
+}
+
+fn play_button_id(file: &Path) -> Option<String> {
+    let library = match Library::of(file)? {
+        Library::Guild(_) => "g",
+        Library::Shared => "s",
+    };
+    Some(format!(
+        "{PLAY_BUTTON}{library}:{}",
+        clip_id(file.file_name()?)
+    ))
+}
+
+fn page_button_id(
+    page: usize,
+    direction: &str,
+    shuffle: Option<Weighting>,
+    tag: Option<&str>,
+) -> anyhow::Result<String> {
+    let id = format!(
+        "{PAGE_BUTTON}{page}:{direction}:{}:{}",
+        shuffle.map(|w| w.name()).unwrap_or_default(),
+        tag.unwrap_or_default()
+    );
+    anyhow::ensure!(
+        id.len() <= MAX_CUSTOM_ID_LEN,
+        "That tag is too long for a soundboard"
+    );
+    Ok(id)
+}
+
+fn shuffle_button_id(weighting: Weighting, tag: Option<&str>) -> anyhow::Result<String> {
+    let id = format!(
+        "{SHUFFLE_BUTTON}{}:{}",
+        weighting.name(),
+        tag.unwrap_or_default()
+    );
+    anyhow::ensure!(
+        id.len() <= MAX_CUSTOM_ID_LEN,
+        "That tag is too long for a soundboard"
+    );
+    Ok(id)
+}
+
+async fn clips(guild_id: GuildId, tag: Option<&str>) -> io::Result<Vec<(PathBuf, String)>> {
+    Ok(super::tagged_files(guild_id, tag)
+        .await?
+        .into_iter()
+        .filter_map(|f| {
+            let id = play_button_id(&f)?;
+            Some((f, id))
+        })
+        .collect())
+}
+
+async fn page(
+    guild_id: GuildId,
+    page: usize,
+    tag: Option<&str>,
+    shuffle: Option<Weighting>,
+) -> anyhow::Result<(String, Vec<CreateActionRow>)> {
+    let clips = clips(guild_id, tag).await?;
+    if clips.is_empty() {
+        return Err(match tag {
+            Some(tag) => anyhow::anyhow!("No sfx tagged {tag}"),
+            None => anyhow::anyhow!("No sfx to put on a board"),
+        });
+    }
+    let (page, pages, clips) = util::button_page(&clips, page);
+    let buttons = clips
+        .iter()
+        .map(|(file, id)| {
+            let label = file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .chars()
+                .take(MAX_LABEL_LEN)
+                .collect::<String>();
+            CreateButton::new(id)
+                .label(label)
+                .style(ButtonStyle::Secondary)
+        })
+        .collect();
+    let mut controls = util::page_buttons(page, pages, |page, direction| {
+        page_button_id(page, direction, shuffle, tag)
+    })?;
+    if let Some(weighting) = shuffle {
+        controls.push(CreateButton::new(shuffle_button_id(weighting, tag)?).emoji('🔀'));
+    }
+    let rows = util::button_rows(buttons, controls);
+    let content = match tag {
+        Some(tag) => format!("**Soundboard:** {tag} ({}/{pages})", page + 1),
+        None => format!("**Soundboard** ({}/{pages})", page + 1),
+    };
+    Ok((content, rows))
+}
+
+/// Finds the file a play button refers to, making sure it's one the guild can play.
+async fn pressed_clip(guild_id: GuildId, id: &str) -> anyhow::Result<PathBuf> {
+    let (library, clip) = id.split_once(':').context("Invalid button")?;
+    let library = match library {
+        "g" => Library::Guild(guild_id),
+        "s" => Library::Shared,
+        _ => anyhow::bail!("Invalid button"),
+    };
+    anyhow::ensure!(
+        Library::visible_from(guild_id).await?.contains(&library),
+        "This sfx can't be played here"
+    );
+    library
+        .files()?
+        .find(|e| clip_id(&e.file_name()) == clip)
+        .map(|e| e.path())
+        .context("This sfx no longer exists")
+}
+
+/// Picks the sfx to play for a button, either the one on it or a random one for the shuffle button.
+async fn pressed(guild_id: GuildId, custom_id: &str) -> anyhow::Result<PathBuf> {
+    if let Some(id) = custom_id.strip_prefix(PLAY_BUTTON) {
+        pressed_clip(guild_id, id).await
+    } else {
+        let id = custom_id
+            .strip_prefix(SHUFFLE_BUTTON)
+            .context("Invalid button")?;
+        let (weighting, tag) = id.split_once(':').context("Invalid button")?;
+        let weighting = Weighting::from_name(weighting).context("Invalid button")?;
+        random::choose(guild_id, Some(tag).filter(|t| !t.is_empty()), weighting).await
+    }
+}
+
+async fn play(ctx: &Context, press: &ComponentInteraction, guild_id: GuildId) {
+    util::acknowledge_press(ctx, press, async {
+        let member = press.member.as_deref().context("Not in a guild")?;
+        permissions::ensure_can_play(&ctx.cache, guild_id, member).await?;
+        let file = pressed(guild_id, &press.data.custom_id).await?;
+        let bot = util::bot(ctx).await?;
+        super::play_clip(ctx, &bot, guild_id, press.user.id, &file).await?;
+        permissions::start_cooldown(guild_id, press.user.id);
+        Ok(())
+    })
+    .await;
+}
+
+async fn turn_page(ctx: &Context, press: &ComponentInteraction, guild_id: GuildId, id: &str) {
+    let mut parts = id.splitn(4, ':');
+    let page_number = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    let shuffle = parts.nth(1).and_then(Weighting::from_name);
+    let tag = parts.next().filter(|t| !t.is_empty());
+    util::turn_page(ctx, press, page(guild_id, page_number, tag, shuffle).await).await;
+}
+
+async fn handle_press(ctx: &Context, i: &Interaction) {
+    let Some(press) = i.as_message_component() else {
+        return;
+    };
+    let Some(guild_id) = press.guild_id else {
+        return;
+    };
+    if press.data.custom_id.starts_with(PLAY_BUTTON)
+        || press.data.custom_id.starts_with(SHUFFLE_BUTTON)
+    {
+        play(ctx, press, guild_id).await;
+    } else if let Some(id) = press.data.custom_id.strip_prefix(PAGE_BUTTON) {
+        turn_page(ctx, press, guild_id, id).await;
+    }
+}
+
+pub(super) async fn initialize(events: &pubsub::EventBus) {
+    events
+        .subscribe::<events::InteractionCreate, _>(|ctx, i| {
+            async move {
+                handle_press(&ctx.serenity, i).await;
+                ControlFlow::Continue(())
+            }
+            .boxed()
+        })
+        .await;
+}
@@ -0,0 +1,199 @@
+use super::{
+    library::Library,
+    metadata::key,
+    permissions::{self, Action},
+};
+use crate::{in_files, prefs};
+use anyhow::Context as _;
+use json_db::GlobalDatabase;
+use poise::command;
+use serenity::all::{Context, GuildId, UserId, VoiceState};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    io,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The intro of each member of each guild, by the path of the clip.
+static INTROS: GlobalDatabase<HashMap<GuildId, HashMap<UserId, String>>> =
+    GlobalDatabase::new(in_files!("sfx_intros.json"));
+
+/// How long before someone's intro plays again.
+const INTRO_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+static LAST_PLAYED: LazyLock<Mutex<HashMap<(GuildId, UserId), Instant>>> =
+    LazyLock::new(Default::default);
+
+/// Manage the sfx that plays when you join a voice channel
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("intro_set", "intro_clear", "intro_enabled")
+)]
+pub(super) async fn intro(_: super::super::Context<'_>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Choose the sfx that plays when you join a voice channel
+#[command(slash_command, guild_only, rename = "set")]
+async fn intro_set(
+    ctx: super::super::Context<'_>,
+    #[autocomplete = "super::autocomplete_sfx"] query: String,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let file = super::find_file(guild_id, &query).await?;
+    INTROS
+        .load()
+        .await?
+        .entry(guild_id)
+        .or_default()
+        .insert(ctx.author().id, key(&file));
+    let enabled = prefs::guild::get(guild_id)
+        .await?
+        .is_some_and(|p| p.sfx_intros);
+    ctx.say(format!(
+        "**{}** will play when you join a voice channel{}",
+        file.file_name().unwrap().display(),
+        if enabled {
+            ""
+        } else {
+            ", once intros are enabled in this server"
+        }
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Stop playing an sfx when you join a voice channel
+#[command(slash_command, guild_only, rename = "clear")]
+async fn intro_clear(ctx: super::super::Context<'_>) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let removed = INTROS
+        .load()
+        .await?
+        .get_mut(&guild_id)
+        .and_then(|intros| intros.remove(&ctx.author().id))
+        .is_some();
+    ctx.say(if removed {
+        "Intro removed"
+    } else {
+        "You don't have an intro"
+    })
+    .await?;
+    Ok(())
+}
+
+/// Choose whether members' intros play in this server
+#[command(
+    slash_command,
+    guild_only,
+    rename = "enabled",
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn intro_enabled(ctx: super::super::Context<'_>, enabled: bool) -> anyhow::Result<()> {
+    prefs::guild::update(ctx.guild_id().context("Not in a guild")?, |p| {
+        p.sfx_intros = enabled
+    })
+    .await?;
+    ctx.say(if enabled {
+        "Intros enabled"
+    } else {
+        "Intros disabled"
+    })
+    .await?;
+    Ok(())
+}
+
+/// Keeps intros pointing at a clip that was renamed.
+pub(super) async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    let (from, to) = (key(from), key(to));
+    for intro in INTROS
+        .load()
+        .await?
+        .values_mut()
+        .flat_map(|i| i.values_mut())
+    {
+        if *intro == from {
+            *intro = to.clone();
+        }
+    }
+    Ok(())
+}
+
+async fn intro_of(guild_id: GuildId, user: UserId) -> io::Result<Option<PathBuf>> {
+    Ok(INTROS
+        .load()
+        .await?
+        .get(&guild_id)
+        .and_then(|intros| intros.get(&user))
+        .map(PathBuf::from))
+}
+
+/// Starts the cooldown of someone's intro, returns false if it's still cooling down.
+fn cool_down(guild_id: GuildId, user: UserId) -> bool {
+    let mut last_played = LAST_PLAYED.lock().unwrap();
+    let now = Instant::now();
+    last_played.retain(|_, at| now.duration_since(*at) < INTRO_COOLDOWN);
+    match last_played.entry((guild_id, user)) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(e) => {
+            e.insert(now);
+            true
+        }
+    }
+}
+
+/// Plays the intro of whoever just joined a voice channel.
+pub(super) async fn greet(
+    ctx: &Context,
+    old: Option<&VoiceState>,
+    new: &VoiceState,
+) -> anyhow::Result<()> {
+    let (Some(guild_id), Some(channel)) = (new.guild_id, new.channel_id) else {
+        return Ok(());
+    };
+    if old.and_then(|vs| vs.channel_id) == Some(channel)
+        || new.member.as_ref().is_some_and(|m| m.user.bot)
+    {
+        return Ok(());
+    }
+    let prefs = prefs::guild::get(guild_id).await?.unwrap_or_default();
+    // whoever joins the disconnect channel gets its own sfx, and a goodbye
+    if !prefs.sfx_intros
+        || prefs
+            .disconnect_channel
+            .is_some_and(|d| d.channel == channel)
+    {
+        return Ok(());
+    }
+    let Some(file) = intro_of(guild_id, new.user_id).await? else {
+        return Ok(());
+    };
+    if let Some(member) = &new.member
+        && permissions::ensure_allowed(&ctx.cache, guild_id, member, Action::Play)
+            .await
+            .is_err()
+    {
+        return Ok(());
+    }
+    // the clip may have been deleted, or its library hidden, since it was chosen
+    let visible = match Library::of(&file) {
+        Some(library) => Library::visible_from(guild_id).await?.contains(&library),
+        None => false,
+    };
+    if !visible || !Path::exists(&file) {
+        return Ok(());
+    }
+    if super::util::busy_elsewhere(ctx, guild_id, channel).await? {
+        return Ok(());
+    }
+    if !cool_down(guild_id, new.user_id) {
+        return Ok(());
+    }
+    let bot = super::util::bot(ctx).await?;
+    // intros aren't picked by anyone, so they don't count towards the stats
+    super::play_file(ctx, &bot, guild_id, new.user_id, &file).await?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use crate::{in_files, prefs};
+use serenity::model::id::GuildId;
+use std::{
+    fs::{self, DirBuilder, DirEntry},
+    io,
+    path::{Path, PathBuf},
+};
+
+const SFX_FILES_DIR: &str = in_files!("sfx");
+const SHARED_LIBRARY: &str = "shared";
+const MAX_NAME_LEN: usize = 64;
+/// The guild whose sfx were in [`SFX_FILES_DIR`] before libraries existed.
+const LEGACY_GUILD: GuildId = GuildId::new(136220994812641280);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Library {
+    Guild(GuildId),
+    Shared,
+}
+
+impl Library {
+    /// The libraries a guild can play sfx from, in order of precedence.
+    pub async fn visible_from(guild: GuildId) -> io::Result<Vec<Self>> {
+        let hide_shared = prefs::guild::get(guild)
+            .await?
+            .is_some_and(|p| p.sfx_hide_shared_library);
+        let mut libraries = vec![Self::Guild(guild)];
+        if !hide_shared {
+            libraries.push(Self::Shared);
+        }
+        Ok(libraries)
+    }
+
+    /// Finds which library a file belongs to.
+    pub fn of(file: &Path) -> Option<Self> {
+        let dir = file.parent()?;
+        if dir.parent()? != Path::new(SFX_FILES_DIR) {
+            return None;
+        }
+        Self::from_dir_name(dir.file_name()?.to_str()?)
+    }
+
+    fn from_dir_name(name: &str) -> Option<Self> {
+        match name {
+            SHARED_LIBRARY => Some(Self::Shared),
+            id => id
+                .parse()
+                .ok()
+                .filter(|&id| id != 0)
+                .map(|id| Self::Guild(GuildId::new(id))),
+        }
+    }
+
+    /// Every library that has been created so far.
+    pub fn all() -> io::Result<Vec<Self>> {
+        Ok(fs::read_dir(SFX_FILES_DIR)?
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+            .filter_map(|e| Self::from_dir_name(e.file_name().to_str()?))
+            .collect())
+    }
+
+    pub fn dir(self) -> PathBuf {
+        match self {
+            Self::Guild(gid) => [SFX_FILES_DIR, &gid.to_string()].iter().collect(),
+            Self::Shared => [SFX_FILES_DIR, SHARED_LIBRARY].iter().collect(),
+        }
+    }
+
+    /// The path to a file in this library, creating the library if it doesn't exist yet.
+    pub fn path<S: AsRef<str>, F: Into<Option<S>>>(self, file: F) -> io::Result<PathBuf> {
+        let dir = self.dir();
+        DirBuilder::new().recursive(true).create(&dir)?;
+        Ok(match file.into() {
+            Some(f) => dir.join(f.as_ref()),
+            None => dir,
+        })
+    }
+
+    pub fn files(self) -> io::Result<impl Iterator<Item = DirEntry>> {
+        Ok(fs::read_dir(self.path::<&str, _>(None)?)?
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_file())))
+    }
+}
+
+/// Turns a user provided name into a file name that stays inside its library.
+pub fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = name
+        .rsplit(['/', '\\'])
+        .next()?
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim().trim_start_matches('.');
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    };
+    let stem = stem.chars().take(MAX_NAME_LEN).collect::<String>();
+    let stem = stem.trim();
+    if stem.is_empty() {
+        return None;
+    }
+    Some(match ext {
+        Some(ext) if !ext.is_empty() => format!("{stem}.{ext}"),
+        _ => stem.to_owned(),
+    })
+}
+
+/// Moves the sfx from before libraries existed into the library of the guild they were made for.
+pub(super) async fn migrate() -> io::Result<()> {
+    let library = Library::Guild(LEGACY_GUILD).path::<&str, _>(None)?;
+    let mut moved = Vec::new();
+    for entry in fs::read_dir(SFX_FILES_DIR)?.filter_map(Result::ok) {
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let new_path = library.join(entry.file_name());
+        tracing::info!("moving {:?} to {:?}", entry.path(), new_path);
+        fs::rename(entry.path(), &new_path)?;
+        moved.push((entry.path(), new_path));
+    }
+    if !moved.is_empty() {
+        super::stats::migrate(LEGACY_GUILD, &moved).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::sanitize_file_name;
+
+    #[test]
+    fn sanitize_keeps_plain_names() {
+        assert_eq!(
+            sanitize_file_name("vine boom.mp3").as_deref(),
+            Some("vine boom.mp3")
+        );
+    }
+
+    #[test]
+    fn sanitize_strips_paths() {
+        assert_eq!(
+            sanitize_file_name("../../config.toml").as_deref(),
+            Some("config.toml")
+        );
+        assert_eq!(
+            sanitize_file_name("..\\evil.ogg").as_deref(),
+            Some("evil.ogg")
+        );
+        assert_eq!(sanitize_file_name("a/.."), None);
+    }
+
+    #[test]
+    fn sanitize_rejects_hidden_and_empty_names() {
+        assert_eq!(sanitize_file_name(".hidden").as_deref(), Some("hidden"));
+        assert_eq!(sanitize_file_name("..."), None);
+        assert_eq!(sanitize_file_name(""), None);
+    }
+
+    #[test]
+    fn sanitize_replaces_weird_characters() {
+        assert_eq!(
+            sanitize_file_name("a*b?c:.wav").as_deref(),
+            Some("a_b_c_.wav")
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use super::{audio, library::Library};
+use crate::in_files;
+use json_db::GlobalDatabase;
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+    path::Path,
+};
+
+/// Everything known about an sfx besides its audio, keyed by the path of the clip.
+static METADATA: GlobalDatabase<HashMap<String, ClipMeta>> =
+    GlobalDatabase::new(in_files!("sfx_metadata.json"));
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct ClipMeta {
+    /// Volume that normalises the clip's loudness.
+    #[serde(default)]
+    pub gain: Option<f32>,
+    /// Volume chosen by an admin, takes precedence over `gain`.
+    #[serde(default)]
+    pub volume: Option<f32>,
+    /// Other names the clip can be played by.
+    #[serde(default)]
+    pub aliases: BTreeSet<String>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub uploader: Option<UserId>,
+    /// Locked clips can only be changed by whoever uploaded them and admins.
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl ClipMeta {
+    pub fn playback_volume(&self) -> f32 {
+        self.volume.or(self.gain).unwrap_or(1.0)
+    }
+}
+
+pub(super) fn key(clip: &Path) -> String {
+    clip.to_string_lossy().into_owned()
+}
+
+pub async fn all() -> io::Result<HashMap<String, ClipMeta>> {
+    let metadata = METADATA.load().await?;
+    Ok(HashMap::clone(&metadata))
+}
+
+pub async fn get(clip: &Path) -> io::Result<ClipMeta> {
+    Ok(METADATA
+        .load()
+        .await?
+        .get(&key(clip))
+        .cloned()
+        .unwrap_or_default())
+}
+
+pub async fn update<F, R>(clip: &Path, f: F) -> io::Result<R>
+where
+    F: FnOnce(&mut ClipMeta) -> R,
+{
+    Ok(f(METADATA.load().await?.entry(key(clip)).or_default()))
+}
+
+/// Moves the metadata of a clip that was renamed.
+pub async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    let mut metadata = METADATA.load().await?;
+    if let Some(meta) = metadata.remove(&key(from)) {
+        metadata.insert(key(to), meta);
+    }
+    Ok(())
+}
+
+pub async fn remove(clip: &Path) -> io::Result<Option<ClipMeta>> {
+    Ok(METADATA.load().await?.remove(&key(clip)))
+}
+
+/// Measures the gain of every clip that was added before loudness was being measured.
+pub(super) async fn backfill_gains() -> io::Result<()> {
+    let mut missing = Vec::new();
+    {
+        let metadata = METADATA.load().await?;
+        for library in Library::all()? {
+            missing.extend(
+                library
+                    .files()?
+                    .map(|e| e.path())
+                    .filter(|p| metadata.get(&key(p)).is_none_or(|m| m.gain.is_none())),
+            );
+        }
+    }
+    for clip in missing {
+        let bytes = match tokio::fs::read(&clip).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!("can't read {clip:?} to measure its loudness: {e}");
+                continue;
+            }
+        };
+        let extension = clip.extension().and_then(|e| e.to_str()).map(str::to_owned);
+        let analysis =
+            tokio::task::spawn_blocking(move || audio::analyse(bytes, extension.as_deref()))
+                .await
+                .map_err(io::Error::other)?;
+        match analysis {
+            Ok(analysis) => {
+                let gain = analysis.normalising_gain();
+                update(&clip, |m| m.gain = Some(gain)).await?;
+            }
+            Err(e) => tracing::warn!("can't measure the loudness of {clip:?}: {e:#}"),
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,40 @@
+mod audio;
+mod board;
+mod intro;
+mod library;
+mod metadata;
+mod permissions;
+mod random;
+mod stats;
+mod trash;
 pub mod util;
 
-use crate::in_files;
+use crate::{
+    features::{disconnect_channel, moderation::mod_log},
+    prefs,
+    prefs::guild::SfxMode,
+    util::daemons::DaemonManager,
+};
 use anyhow::Context as _;
 use chrono::{DateTime, Duration, Utc};
 use daemons::Daemon;
 use itertools::Itertools;
-use json_db::GlobalDatabase;
 use poise::{CreateReply, command};
-use serde::{Deserialize, Serialize};
-use serenity::all::{Attachment, CreateAttachment, CreateEmbed, Http};
-use serenity::model::id::GuildId;
+use serenity::all::{
+    Attachment, AutocompleteChoice, ButtonStyle, ChannelId, CreateActionRow, CreateAttachment,
+    CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, Http,
+    Mentionable as _,
+};
+use serenity::model::id::{GuildId, UserId};
 use simsearch::SimSearch;
-use songbird::input::Input;
+use songbird::{
+    Event, EventContext, TrackEvent,
+    input::Input,
+    tracks::{Track, TrackHandle},
+};
 use std::ops::ControlFlow;
 use std::{
-    collections::HashMap,
-    fs::{self, DirBuilder, OpenOptions},
+    fs::{self, OpenOptions},
     future::Future,
     io::{self, Write},
     path::{Path, PathBuf},
@@ -24,22 +43,18 @@ use std::{
 };
 use tokio::fs::File;
 
-const SFX_FILES_DIR: &str = in_files!("sfx");
+use board::board;
+use intro::intro;
+use library::Library;
+use permissions::{can_delete, can_play, can_upload, cooldown, roles};
+use random::random;
+pub(crate) use stats::StatsPeriod;
+use stats::stats;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct SfxStats(HashMap<String, usize>);
-
-impl SfxStats {
-    fn update(&mut self, sfx: &str) {
-        self.0
-            .entry(sfx.to_string())
-            .and_modify(|c| *c += 1)
-            .or_insert(1);
-    }
-}
-
-static SFX_STATS: GlobalDatabase<SfxStats> = GlobalDatabase::new(in_files!("sfx_stats.json"));
+const MAX_SFX_DURATION: StdDuration = StdDuration::from_secs(30);
+const MAX_UPLOAD_SIZE: u32 = 1024 * 1024;
+/// Recordings that are going to be cut can be longer, since only part of them is kept.
+const MAX_UNCUT_UPLOAD_SIZE: u32 = 10 * 1024 * 1024;
 
 #[derive(Debug)]
 pub struct LeaveVoice {
@@ -53,6 +68,13 @@ impl Daemon<false> for LeaveVoice {
     type Data = (Arc<serenity::cache::Cache>, Arc<Http>);
 
     async fn run(&mut self, _: &Self::Data) -> daemons::ControlFlow {
+        if let Some(call) = self.songbird.get(self.guild_id)
+            && !call.lock().await.queue().is_empty()
+        {
+            // the end of the queue will schedule leaving again
+            self.when = Utc::now() + Duration::minutes(1);
+            return ControlFlow::Continue(());
+        }
         tracing::debug!("Leaving voice. Scheduled for {}", self.when);
         if let Err(e) = self.songbird.remove(self.guild_id).await {
             tracing::error!("Could not leave voice channel: {}", e);
@@ -72,106 +94,541 @@ impl Daemon<false> for LeaveVoice {
 #[command(
     slash_command,
     guild_only,
-    subcommands("stop", "play", "list", "add", "delete", "stats", "download")
+    subcommands(
+        "stop", "play", "list", "add", "delete", "stats", "download", "shared", "queue", "skip",
+        "clear", "mode", "volume", "rename", "alias", "tag", "board", "intro", "restore", "trim",
+        "random", "roles", "cooldown", "lock", "log"
+    )
 )]
 pub async fn sfx(_: super::Context<'_>) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub async fn initialize(daemons: &mut DaemonManager, events: &pubsub::EventBus) {
+    if let Err(e) = library::migrate().await {
+        tracing::error!("Failed to move old sfx into a library: {}", e);
+    }
+    daemons.add_daemon(trash::TrashPurger).await;
+    board::initialize(events).await;
+    util::init_voice_events().await;
+    tokio::spawn(async {
+        if let Err(e) = metadata::backfill_gains().await {
+            tracing::error!("Failed to measure the loudness of existing sfx: {}", e);
+        }
+    });
+}
+
 /// Play a saved sfx!
-#[command(slash_command, guild_only)]
-async fn play(ctx: super::Context<'_>, query: String) -> anyhow::Result<()> {
+#[command(slash_command, guild_only, check = "can_play")]
+async fn play(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+) -> anyhow::Result<()> {
     play_impl(ctx, &query).await
 }
 
 /// Stops everything
 #[command(slash_command, guild_only)]
 async fn stop(ctx: super::Context<'_>) -> anyhow::Result<()> {
-    if let Some(call) = songbird::get(ctx.serenity_context())
+    let call = current_call(ctx).await?;
+    let mut call = call.lock().await;
+    call.queue().stop();
+    call.stop();
+    Ok(())
+}
+
+/// Show the queued sfx
+#[command(slash_command, guild_only)]
+async fn queue(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let queue = current_call(ctx)
+        .await?
+        .lock()
         .await
-        .context("Songbird not initialized")?
-        .get(ctx.guild_id().unwrap())
-    {
-        call.lock().await.stop();
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("Not in a voice channel"))
+        .queue()
+        .current_queue();
+    let mut tracks = queue.iter().map(|t| t.data::<String>());
+    match tracks.next() {
+        Some(playing) => {
+            ctx.say(format!(
+                "**Playing:** {playing}\n{}",
+                tracks
+                    .enumerate()
+                    .format_with("\n", |(i, t), f| f(&format_args!("{}. {t}", i + 1)))
+            ))
+            .await?
+        }
+        None => ctx.say("Nothing queued").await?,
+    };
+    Ok(())
+}
+
+/// Skip the sfx that's playing and play the next one in the queue
+#[command(slash_command, guild_only)]
+async fn skip(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    current_call(ctx).await?.lock().await.queue().skip()?;
+    ctx.say("Skipped").await?;
+    Ok(())
+}
+
+/// Remove every queued sfx, letting the current one finish
+#[command(slash_command, guild_only)]
+async fn clear(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let call = current_call(ctx).await?;
+    let call = call.lock().await;
+    let mut cleared = 0;
+    while let Some(queued) = call.queue().dequeue(1) {
+        let _ = queued.stop();
+        cleared += 1;
     }
+    ctx.say(format!("Cleared {cleared} sfx from the queue"))
+        .await?;
+    Ok(())
+}
+
+/// Choose whether sfx play over each other or wait in a queue
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn mode(ctx: super::Context<'_>, mode: SfxMode) -> anyhow::Result<()> {
+    prefs::guild::update(ctx.guild_id().context("Not in a guild")?, |p| {
+        p.sfx_mode = mode
+    })
+    .await?;
+    ctx.say(match mode {
+        SfxMode::Overlap => "Sfx will now play over each other",
+        SfxMode::Queue => "Sfx will now wait for their turn",
+    })
+    .await?;
+    Ok(())
+}
+
+async fn current_call(
+    ctx: super::Context<'_>,
+) -> anyhow::Result<Arc<tokio::sync::Mutex<songbird::Call>>> {
+    songbird::get(ctx.serenity_context())
+        .await
+        .context("Songbird not initialized")?
+        .get(ctx.guild_id().context("Not in a guild")?)
+        .context("Not in a voice channel")
 }
 
 /// List the available sfx files
 #[command(slash_command, guild_only)]
-async fn list(ctx: super::Context<'_>) -> anyhow::Result<()> {
-    let sounds = fs::read_dir(sfx_path::<&str, _>(None).await?).map(|x| {
-        let mut files = x
-            .filter_map(Result::ok)
-            .map(|x| String::from(x.path().as_path().file_name().unwrap().to_string_lossy()))
+async fn list(
+    ctx: super::Context<'_>,
+    #[description = "Only list sfx with this tag"] tag: Option<String>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let tag = tag.map(|t| normalize_label(&t));
+    let metadata = metadata::all().await?;
+    let mut pages = Vec::new();
+    for library in Library::visible_from(guild_id).await? {
+        let title = match library {
+            Library::Guild(_) => "List of sfx:",
+            Library::Shared => "List of shared sfx:",
+        };
+        let mut files = library
+            .files()?
+            .filter(|x| {
+                tag.as_ref().is_none_or(|tag| {
+                    metadata
+                        .get(&*x.path().to_string_lossy())
+                        .is_some_and(|m| m.tags.contains(tag))
+                })
+            })
+            .map(|x| String::from(x.file_name().to_string_lossy()))
             .map(unicase::UniCase::new)
             .collect::<Vec<_>>();
         files.sort_unstable();
-        files
-    });
-    match sounds {
-        Ok(sounds) if !sounds.is_empty() => {
-            util::paginate(
-                ctx,
-                &sounds
-                    .iter()
-                    .chunks(30)
-                    .into_iter()
-                    .map(|x| {
-                        let f = x.collect::<Vec<_>>();
-                        let c1 = f[0].to_uppercase().chars().next().unwrap();
-                        let c2 = f[f.len() - 1].to_uppercase().chars().next().unwrap();
-                        CreateEmbed::new().title("List of sfx:").field(
-                            format!("{c1}-{c2}"),
-                            f.iter().fold(String::new(), |acc, x| acc + "\n" + x),
-                            true,
-                        )
-                    })
-                    .collect::<Vec<_>>(),
+        pages.extend(files.iter().chunks(30).into_iter().map(|x| {
+            let f = x.collect::<Vec<_>>();
+            let c1 = f[0].to_uppercase().chars().next().unwrap();
+            let c2 = f[f.len() - 1].to_uppercase().chars().next().unwrap();
+            CreateEmbed::new().title(title).field(
+                format!("{c1}-{c2}"),
+                f.iter().fold(String::new(), |acc, x| acc + "\n" + x),
+                true,
             )
-            .await?
-        }
-        Err(_) | Ok(_) => {
-            ctx.say("No files :(").await?;
-        }
-    };
+        }));
+    }
+    if pages.is_empty() {
+        match tag {
+            Some(tag) => ctx.say(format!("No sfx tagged {tag} :(")).await?,
+            None => ctx.say("No files :(").await?,
+        };
+    } else {
+        util::paginate(ctx, &pages).await?;
+    }
     Ok(())
 }
 
 /// Saves a new sfx file
-#[command(slash_command, guild_only)]
-async fn add(ctx: super::Context<'_>, attachment: Attachment) -> anyhow::Result<()> {
-    if !cfg!(debug_assertions) && attachment.size > 1024 * 1024 {
+#[command(slash_command, guild_only, check = "can_upload")]
+async fn add(
+    ctx: super::Context<'_>,
+    attachment: Attachment,
+    #[description = "Name to save the sfx as, defaults to the file's name"] name: Option<String>,
+    #[description = "Where the sfx starts in the file, like 1:02.5"] start: Option<String>,
+    #[description = "Where the sfx ends in the file, like 1:04"] end: Option<String>,
+    #[description = "Seconds to fade in for"] fade_in: Option<f64>,
+    #[description = "Seconds to fade out for"] fade_out: Option<f64>,
+    #[description = "Add it for every server, bot owners only"] shared: Option<bool>,
+) -> anyhow::Result<()> {
+    let library = if shared.unwrap_or(false) {
+        anyhow::ensure!(
+            ctx.framework().options.owners.contains(&ctx.author().id),
+            "Only the bot owners can add shared sfx"
+        );
+        Library::Shared
+    } else {
+        Library::Guild(ctx.guild_id().context("Not in a guild")?)
+    };
+    let cut = cut_options(start, end, fade_in, fade_out)?;
+    let max_size = if cut.is_some() {
+        MAX_UNCUT_UPLOAD_SIZE
+    } else {
+        MAX_UPLOAD_SIZE
+    };
+    if !cfg!(debug_assertions) && attachment.size > max_size {
+        return Err(anyhow::anyhow!(
+            "File size too high, please keep it under {}Mb.",
+            max_size / 1024 / 1024
+        ));
+    }
+    let extension = Path::new(&attachment.filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_owned);
+    let file_name = library::sanitize_file_name(name.as_deref().unwrap_or(&attachment.filename))
+        .context("Invalid file name")?;
+    let file_name = match &extension {
+        Some(ext) if Path::new(&file_name).extension().is_none() => format!("{file_name}.{ext}"),
+        _ => file_name,
+    };
+    // cut clips are re-encoded as wav
+    let file_name = match cut {
+        Some(_) => Path::new(&file_name)
+            .with_extension("wav")
+            .to_string_lossy()
+            .into_owned(),
+        None => file_name,
+    };
+    let (bytes, analysis) = prepare(attachment.download().await?, extension, cut)
+        .await
+        .with_context(|| format!("{} can't be played", attachment.filename))?;
+    if analysis.duration > MAX_SFX_DURATION {
         return Err(anyhow::anyhow!(
-            "File size too high, please keep it under 1Mb."
+            "Sfx is too long ({:.1}s), please keep it under {}s.",
+            analysis.duration.as_secs_f64(),
+            MAX_SFX_DURATION.as_secs(),
         ));
     }
-    let bytes = attachment.download().await?;
-    let path = sfx_path(&attachment.filename).await?;
-    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let path = library.path(&file_name)?;
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(anyhow::anyhow!(
+                "There's already an sfx called {file_name}, use the `name` option to save it with a different name."
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    };
     file.write_all(&bytes)?;
-    ctx.say("File added!").await?;
+    let gain = analysis.normalising_gain();
+    metadata::update(&path, |m| {
+        m.gain = Some(gain);
+        m.uploader = Some(ctx.author().id);
+    })
+    .await?;
+    ctx.say(format!(
+        "Added **{file_name}** ({}, {:.1}s)",
+        analysis.codec,
+        analysis.duration.as_secs_f64()
+    ))
+    .await?;
     Ok(())
 }
 
+/// Cut part of an sfx, the uncut one is kept in the trash
+#[command(slash_command, guild_only, check = "can_delete")]
+async fn trim(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    #[description = "Where the sfx should start, like 0:01.5"] start: Option<String>,
+    #[description = "Where the sfx should end, like 0:04"] end: Option<String>,
+    #[description = "Seconds to fade in for"] fade_in: Option<f64>,
+    #[description = "Seconds to fade out for"] fade_out: Option<f64>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let file = find_file(guild_id, &query).await?;
+    ensure_can_edit(ctx, &file).await?;
+    let cut = cut_options(start, end, fade_in, fade_out)?
+        .context("Say where the sfx should start or end, or how it should fade")?;
+    let extension = file.extension().and_then(|e| e.to_str()).map(str::to_owned);
+    let (bytes, analysis) = prepare(tokio::fs::read(&file).await?, extension, Some(cut)).await?;
+    let new_file = file.with_extension("wav");
+    if new_file != file && new_file.exists() {
+        return Err(anyhow::anyhow!(
+            "There's already an sfx called {}",
+            new_file.file_name().unwrap().display()
+        ));
+    }
+    let meta = metadata::get(&file).await?;
+    // the aliases go to the trimmed clip, the original would have them too once restored
+    metadata::update(&file, |m| m.aliases.clear()).await?;
+    let restore_to = if new_file == file {
+        uncut_name(&file)
+    } else {
+        file.clone()
+    };
+    let trashed = trash::trash_as(&file, &restore_to, ctx.author().id).await?;
+    fs::write(&new_file, &bytes)?;
+    let gain = analysis.normalising_gain();
+    metadata::update(&new_file, |m| {
+        *m = meta.clone();
+        m.gain = Some(gain);
+    })
+    .await?;
+    if new_file != file {
+        stats::rename(&file, &new_file).await?;
+        intro::rename(&file, &new_file).await?;
+        disconnect_channel::sfx_moved(&file, Some(&new_file)).await?;
+    }
+    ctx.say(format!(
+        "Trimmed **{}** to {:.1}s, the original can be restored as **{}** with `/sfx restore {}`",
+        new_file.file_name().unwrap().display(),
+        analysis.duration.as_secs_f64(),
+        trashed.name(),
+        trashed.id(),
+    ))
+    .await?;
+    Ok(())
+}
+
+/// A name no sfx has yet to keep the uncut version of a clip under.
+fn uncut_name(file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| match n {
+            1 => file.with_file_name(format!("{stem} uncut{extension}")),
+            n => file.with_file_name(format!("{stem} uncut {n}{extension}")),
+        })
+        .find(|f| !f.exists())
+        .expect("there are always more names to try")
+}
+
+fn cut_options(
+    start: Option<String>,
+    end: Option<String>,
+    fade_in: Option<f64>,
+    fade_out: Option<f64>,
+) -> anyhow::Result<Option<audio::Cut>> {
+    if start.is_none() && end.is_none() && fade_in.is_none() && fade_out.is_none() {
+        return Ok(None);
+    }
+    let timestamp = |t: &str| {
+        audio::parse_timestamp(t).with_context(|| format!("Invalid time {t:?}, expected M:SS"))
+    };
+    let seconds = |s: f64| {
+        StdDuration::try_from_secs_f64(s).with_context(|| format!("Invalid fade of {s} seconds"))
+    };
+    Ok(Some(audio::Cut {
+        start: start
+            .as_deref()
+            .map(timestamp)
+            .transpose()?
+            .unwrap_or_default(),
+        end: end.as_deref().map(timestamp).transpose()?,
+        fade_in: fade_in.map(seconds).transpose()?.unwrap_or_default(),
+        fade_out: fade_out.map(seconds).transpose()?.unwrap_or_default(),
+    }))
+}
+
+/// Cuts a clip, if asked to, and checks it can be played.
+async fn prepare(
+    bytes: Vec<u8>,
+    extension: Option<String>,
+    cut: Option<audio::Cut>,
+) -> anyhow::Result<(Vec<u8>, audio::Analysis)> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = match &cut {
+            Some(cut) => audio::cut(bytes, extension.as_deref(), cut)?,
+            None => bytes,
+        };
+        let extension = match cut {
+            Some(_) => Some("wav"),
+            None => extension.as_deref(),
+        };
+        let analysis = audio::analyse(bytes.clone(), extension)?;
+        Ok((bytes, analysis))
+    })
+    .await?
+}
+
 /// Remove an sfx file
-#[command(slash_command, guild_only)]
-async fn delete(ctx: super::Context<'_>, query: String) -> anyhow::Result<()> {
-    let file = find_file(&query).await?;
-    ctx.send(CreateReply::default().attachment(
-        CreateAttachment::file(&File::open(&file).await?, file.display().to_string()).await?,
+#[command(slash_command, guild_only, check = "can_delete")]
+async fn delete(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let exact = find_exact(guild_id, &normalize_label(&query)).await?;
+    let file = match &exact {
+        Some(file) => file.clone(),
+        None => find_file(guild_id, &query).await?,
+    };
+    ensure_can_edit(ctx, &file).await?;
+    if exact.is_none() && !confirm_delete(ctx, &file).await? {
+        return Ok(());
+    }
+    let trashed = trash::trash(&file, ctx.author().id).await?;
+    disconnect_channel::sfx_moved(&file, None).await?;
+    ctx.say(format!(
+        "Deleted **{}**, it can be restored with `/sfx restore` for the next 30 days",
+        trashed.name()
     ))
     .await?;
-    fs::remove_file(&file)?;
+    mod_log::log(
+        &ctx.serenity_context().http,
+        guild_id,
+        CreateEmbed::new().title("Sfx deleted").description(format!(
+            "**{}** was deleted by {}",
+            trashed.name(),
+            ctx.author().mention()
+        )),
+    )
+    .await;
     Ok(())
 }
 
+/// Bring back a deleted sfx
+#[command(slash_command, guild_only, check = "can_upload")]
+async fn restore(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_trash"] query: String,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let trashed = trash::find(guild_id, &query).await?;
+    ensure_can_edit(ctx, &trashed.original).await?;
+    trash::restore(&trashed).await?;
+    ctx.say(format!("Restored **{}**", trashed.name())).await?;
+    mod_log::log(
+        &ctx.serenity_context().http,
+        guild_id,
+        CreateEmbed::new()
+            .title("Sfx restored")
+            .description(format!(
+                "**{}**, deleted by {}, was restored by {}",
+                trashed.name(),
+                trashed.deleted_by.mention(),
+                ctx.author().mention()
+            )),
+    )
+    .await;
+    Ok(())
+}
+
+async fn autocomplete_trash(
+    ctx: super::Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let trash = match ctx.guild_id() {
+        Some(guild_id) => trash::visible_from(guild_id).await,
+        None => Ok(Vec::new()),
+    };
+    let partial = partial.to_lowercase();
+    trash
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to autocomplete deleted sfx: {}", e);
+            Vec::new()
+        })
+        .into_iter()
+        .filter(|t| t.name().to_lowercase().contains(&partial))
+        .take(25)
+        .map(|t| {
+            AutocompleteChoice::new(
+                format!("{} (deleted {})", t.name(), t.deleted_at.format("%Y-%m-%d")),
+                t.id(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Asks whether a guessed sfx is really the one that should be deleted.
+async fn confirm_delete(ctx: super::Context<'_>, file: &Path) -> anyhow::Result<bool> {
+    let confirm_id = format!("{}confirm", ctx.id());
+    let cancel_id = format!("{}cancel", ctx.id());
+    let name = file.file_name().unwrap().display();
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(format!("Did you mean **{name}**? It will be deleted."))
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new(&confirm_id)
+                        .label("Delete")
+                        .style(ButtonStyle::Danger),
+                    CreateButton::new(&cancel_id)
+                        .label("Cancel")
+                        .style(ButtonStyle::Secondary),
+                ])]),
+        )
+        .await?;
+    let press = reply
+        .message()
+        .await?
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(StdDuration::from_secs(60))
+        .await;
+    let confirmed = press
+        .as_ref()
+        .is_some_and(|p| p.data.custom_id == confirm_id);
+    let content = if confirmed {
+        format!("Deleting **{name}**")
+    } else {
+        format!("Kept **{name}**")
+    };
+    match press {
+        Some(press) => {
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .content(content)
+                            .components(Vec::new()),
+                    ),
+                )
+                .await?
+        }
+        None => {
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .content(content)
+                        .components(Vec::new()),
+                )
+                .await?
+        }
+    }
+    Ok(confirmed)
+}
+
 /// Download an sfx file
 #[command(slash_command, guild_only)]
-async fn download(ctx: super::Context<'_>, query: String) -> anyhow::Result<()> {
-    let file = find_file(&query).await?;
+async fn download(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+) -> anyhow::Result<()> {
+    let file = find_file(ctx.guild_id().context("Not in a guild")?, &query).await?;
     ctx.send(CreateReply::default().attachment(
         CreateAttachment::file(&File::open(&file).await?, file.display().to_string()).await?,
     ))
@@ -179,138 +636,589 @@ async fn download(ctx: super::Context<'_>, query: String) -> anyhow::Result<()>
     Ok(())
 }
 
-/// Show the stats of the most played sfx
-#[command(slash_command, guild_only)]
-async fn stats(ctx: super::Context<'_>) -> anyhow::Result<()> {
-    let mut stats = SFX_STATS
-        .load()
-        .await?
-        .0
-        .iter()
-        .map(|(k, v)| (k.clone(), *v))
-        .collect::<Vec<(String, usize)>>();
-    ctx.send(CreateReply::default().embed({
-        stats.sort_unstable_by_key(|(_, v)| *v);
-        CreateEmbed::new()
-            .title("Stats")
-            .fields(stats.iter().chunks(12).into_iter().map(|x| {
-                let f = x.collect::<Vec<_>>();
-                let c1 = f[0].1.to_string();
-                let c2 = f[f.len() - 1].1.to_string();
-                (
-                    format!("{}-{}", c1, c2),
-                    f.iter().fold(String::new(), |acc, x| {
-                        acc + "\n"
-                            + &format!(
-                                "{:<5}{}",
-                                x.1,
-                                Path::new(&x.0).file_name().unwrap().to_string_lossy()
-                            )
-                    }),
-                    true,
-                )
-            }))
-    }))
+/// Choose whether this server can use the sfx shared by every server
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn shared(ctx: super::Context<'_>, enabled: bool) -> anyhow::Result<()> {
+    prefs::guild::update(ctx.guild_id().context("Not in a guild")?, |p| {
+        p.sfx_hide_shared_library = !enabled
+    })
+    .await?;
+    ctx.say(if enabled {
+        "Shared sfx enabled"
+    } else {
+        "Shared sfx disabled"
+    })
     .await?;
     Ok(())
 }
 
-async fn play_impl(ctx: super::Context<'_>, search_string: &str) -> anyhow::Result<()> {
-    let mut file = PathBuf::new();
-    play_sfx(ctx, || async {
-        file = find_file(search_string).await?;
-        ctx.say(&format!(
-            "**Playing:** {}",
-            file.file_name().unwrap().display()
-        ))
+/// Choose where deleted and restored sfx are logged, leave it empty to stop logging
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn log(ctx: super::Context<'_>, channel: Option<ChannelId>) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    ctx.say(mod_log::set_channel(guild_id, channel).await?)
         .await?;
-        tracing::info!("Playing sfx: {:?}", file);
-        std::fs::File::open(file.clone()).unwrap();
-        Ok(songbird::input::File::new(file.clone()).into())
+    Ok(())
+}
+
+/// Change how loud an sfx is, leave the volume empty to go back to the normalised volume
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn volume(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    #[description = "Volume in percent of the original clip"]
+    #[max = 400]
+    percent: Option<u32>,
+) -> anyhow::Result<()> {
+    let file = find_file(ctx.guild_id().context("Not in a guild")?, &query).await?;
+    ensure_can_edit(ctx, &file).await?;
+    let name = file.file_name().unwrap().display();
+    let meta = metadata::update(&file, |m| {
+        m.volume = percent.map(|p| p as f32 / 100.0);
+        m.clone()
     })
     .await?;
-    if let Err(e) = SFX_STATS
-        .load()
-        .await
-        .map(|mut s| s.update(file.as_os_str().to_str().unwrap()))
+    ctx.say(match percent {
+        Some(p) => format!("**{name}** will now play at {p}% volume"),
+        None => format!(
+            "**{name}** will now play at its normalised volume ({:.0}%)",
+            meta.playback_volume() * 100.0
+        ),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Give an sfx a new name
+#[command(slash_command, guild_only, check = "can_delete")]
+async fn rename(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    new_name: String,
+) -> anyhow::Result<()> {
+    let file = find_file(ctx.guild_id().context("Not in a guild")?, &query).await?;
+    ensure_can_edit(ctx, &file).await?;
+    let new_name = library::sanitize_file_name(&new_name).context("Invalid file name")?;
+    let new_name = match file.extension() {
+        Some(ext) if Path::new(&new_name).extension().is_none() => {
+            format!("{new_name}.{}", ext.to_string_lossy())
+        }
+        _ => new_name,
+    };
+    let new_file = file.with_file_name(&new_name);
+    if new_file.exists() {
+        return Err(anyhow::anyhow!("There's already an sfx called {new_name}"));
+    }
+    fs::rename(&file, &new_file)?;
+    metadata::rename(&file, &new_file).await?;
+    intro::rename(&file, &new_file).await?;
+    stats::rename(&file, &new_file).await?;
+    disconnect_channel::sfx_moved(&file, Some(&new_file)).await?;
+    ctx.say(format!(
+        "Renamed **{}** to **{new_name}**",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Manage the other names an sfx can be played by
+#[command(slash_command, guild_only, subcommands("alias_add", "alias_remove"))]
+async fn alias(_: super::Context<'_>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Let an sfx also be played by another name
+#[command(slash_command, guild_only, rename = "add")]
+async fn alias_add(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    alias: String,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let file = find_file(guild_id, &query).await?;
+    ensure_can_edit(ctx, &file).await?;
+    let alias = normalize_label(&alias);
+    if alias.is_empty() {
+        return Err(anyhow::anyhow!("Aliases can't be empty"));
+    }
+    if let Some(other) = find_exact(guild_id, &alias).await? {
+        return Err(anyhow::anyhow!(
+            "{alias} already refers to {}",
+            other.file_name().unwrap().display()
+        ));
+    }
+    metadata::update(&file, |m| m.aliases.insert(alias.clone())).await?;
+    ctx.say(format!(
+        "**{}** can now also be played as **{alias}**",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Stop an sfx from being played by one of its aliases
+#[command(slash_command, guild_only, rename = "remove")]
+async fn alias_remove(ctx: super::Context<'_>, alias: String) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let alias = normalize_label(&alias);
+    let file = find_exact(guild_id, &alias)
+        .await?
+        .with_context(|| format!("No sfx has the alias {alias}"))?;
+    ensure_can_edit(ctx, &file).await?;
+    if !metadata::update(&file, |m| m.aliases.remove(&alias)).await? {
+        return Err(anyhow::anyhow!(
+            "{alias} is the name of {}, not an alias",
+            file.file_name().unwrap().display()
+        ));
+    }
+    ctx.say(format!(
+        "**{}** can no longer be played as **{alias}**",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Manage the tags of an sfx
+#[command(slash_command, guild_only, subcommands("tag_add", "tag_remove"))]
+async fn tag(_: super::Context<'_>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Tag an sfx
+#[command(slash_command, guild_only, rename = "add")]
+async fn tag_add(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    tag: String,
+) -> anyhow::Result<()> {
+    let file = find_file(ctx.guild_id().context("Not in a guild")?, &query).await?;
+    ensure_can_edit(ctx, &file).await?;
+    let tag = normalize_label(&tag);
+    if tag.is_empty() {
+        return Err(anyhow::anyhow!("Tags can't be empty"));
+    }
+    metadata::update(&file, |m| m.tags.insert(tag.clone())).await?;
+    ctx.say(format!(
+        "Tagged **{}** with {tag}",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Remove a tag from an sfx
+#[command(slash_command, guild_only, rename = "remove")]
+async fn tag_remove(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    tag: String,
+) -> anyhow::Result<()> {
+    let file = find_file(ctx.guild_id().context("Not in a guild")?, &query).await?;
+    ensure_can_edit(ctx, &file).await?;
+    let tag = normalize_label(&tag);
+    if !metadata::update(&file, |m| m.tags.remove(&tag)).await? {
+        return Err(anyhow::anyhow!(
+            "{} isn't tagged with {tag}",
+            file.file_name().unwrap().display()
+        ));
+    }
+    ctx.say(format!(
+        "Removed the tag {tag} from **{}**",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Shared sfx can only be changed by the bot owners, locked ones by their uploader and admins.
+async fn ensure_can_edit(ctx: super::Context<'_>, file: &Path) -> anyhow::Result<()> {
+    let name = file.file_name().unwrap().display();
+    if Library::of(file) == Some(Library::Shared)
+        && !ctx.framework().options.owners.contains(&ctx.author().id)
     {
-        tracing::error!("Failed to update sfx stats: {}", e);
+        return Err(anyhow::anyhow!(
+            "{name} is a shared sfx, only the bot owners can change it"
+        ));
     }
+    let meta = metadata::get(file).await?;
+    if meta.locked && meta.uploader != Some(ctx.author().id) && !is_admin(ctx).await {
+        return Err(anyhow::anyhow!(
+            "{name} is locked, only whoever uploaded it and admins can change it"
+        ));
+    }
+    Ok(())
+}
+
+async fn is_admin(ctx: super::Context<'_>) -> bool {
+    match (ctx.guild_id(), ctx.author_member().await) {
+        (Some(guild_id), Some(member)) => permissions::is_admin(ctx.cache(), guild_id, &member),
+        _ => false,
+    }
+}
+
+/// Stop other members from changing or deleting an sfx you uploaded
+#[command(slash_command, guild_only)]
+async fn lock(
+    ctx: super::Context<'_>,
+    #[autocomplete = "autocomplete_sfx"] query: String,
+    locked: bool,
+) -> anyhow::Result<()> {
+    let file = find_file(ctx.guild_id().context("Not in a guild")?, &query).await?;
+    let name = file.file_name().unwrap().display();
+    let meta = metadata::get(&file).await?;
+    if meta.uploader != Some(ctx.author().id) && !is_admin(ctx).await {
+        return Err(anyhow::anyhow!(
+            "Only whoever uploaded {name} and admins can lock it"
+        ));
+    }
+    metadata::update(&file, |m| m.locked = locked).await?;
+    ctx.say(if locked {
+        format!("**{name}** is now locked")
+    } else {
+        format!("**{name}** is no longer locked")
+    })
+    .await?;
     Ok(())
 }
 
-pub(crate) async fn play_sfx<F, Fut>(ctx: super::Context<'_>, audio_source: F) -> anyhow::Result<()>
+/// Aliases and tags are case insensitive single words.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+async fn play_impl(ctx: super::Context<'_>, search_string: &str) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let file = find_file(guild_id, search_string).await?;
+    play_clip(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild_id,
+        ctx.author().id,
+        &file,
+    )
+    .await?;
+    permissions::start_cooldown(guild_id, ctx.author().id);
+    ctx.say(&format!(
+        "**Playing:** {}",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Plays a saved sfx at its volume and counts it in the stats.
+pub(crate) async fn play_clip(
+    ctx: &serenity::all::Context,
+    bot: &crate::Bot,
+    guild_id: GuildId,
+    user: UserId,
+    file: &Path,
+) -> anyhow::Result<TrackHandle> {
+    let handle = play_file(ctx, bot, guild_id, user, file).await?;
+    if let Err(e) = stats::record(file, guild_id, user).await {
+        tracing::error!("Failed to update sfx stats: {}", e);
+    }
+    Ok(handle)
+}
+
+/// Plays a saved sfx at its volume, in the user's voice channel, without counting it.
+pub(crate) async fn play_file(
+    ctx: &serenity::all::Context,
+    bot: &crate::Bot,
+    guild_id: GuildId,
+    user: UserId,
+    file: &Path,
+) -> anyhow::Result<TrackHandle> {
+    play_in_voice(ctx, bot, guild_id, user, || async {
+        tracing::info!("Playing sfx: {:?}", file);
+        std::fs::File::open(file)?;
+        Ok(Sound {
+            name: file.file_name().unwrap().to_string_lossy().into_owned(),
+            input: songbird::input::File::new(file.to_owned()).into(),
+            volume: metadata::get(file).await?.playback_volume(),
+        })
+    })
+    .await
+}
+
+/// Something to play, named so it can be identified while it's waiting in the queue.
+pub(crate) struct Sound {
+    pub name: String,
+    pub input: Input,
+    pub volume: f32,
+}
+
+pub(crate) async fn play_sfx<F, Fut>(
+    ctx: super::Context<'_>,
+    audio_source: F,
+) -> anyhow::Result<TrackHandle>
 where
     F: FnOnce() -> Fut,
-    Fut: Future<Output = anyhow::Result<Input>>,
+    Fut: Future<Output = anyhow::Result<Sound>>,
 {
     let guild_id = ctx.guild_id().context("Not in a guild")?;
+    play_in_voice(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild_id,
+        ctx.author().id,
+        audio_source,
+    )
+    .await
+}
+
+/// Plays a sound in `user`'s voice channel, or the bot's current call.
+pub(crate) async fn play_in_voice<F, Fut>(
+    ctx: &serenity::all::Context,
+    bot: &crate::Bot,
+    guild_id: GuildId,
+    user: UserId,
+    audio_source: F,
+) -> anyhow::Result<TrackHandle>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<Sound>>,
+{
+    let mode = prefs::guild::get(guild_id)
+        .await?
+        .map(|p| p.sfx_mode)
+        .unwrap_or_default();
+    play_with_mode(ctx, bot, guild_id, user, mode, audio_source).await
+}
+
+/// Like [play_in_voice], but always queues.
+pub(crate) async fn enqueue_in_voice<F, Fut>(
+    ctx: &serenity::all::Context,
+    bot: &crate::Bot,
+    guild_id: GuildId,
+    user: UserId,
+    audio_source: F,
+) -> anyhow::Result<TrackHandle>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<Sound>>,
+{
+    play_with_mode(ctx, bot, guild_id, user, SfxMode::Queue, audio_source).await
+}
+
+async fn play_with_mode<F, Fut>(
+    ctx: &serenity::all::Context,
+    bot: &crate::Bot,
+    guild_id: GuildId,
+    user: UserId,
+    mode: SfxMode,
+    audio_source: F,
+) -> anyhow::Result<TrackHandle>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<Sound>>,
+{
+    let call_lock = util::join_or_get_call(ctx, guild_id, user).await?;
+    let Sound {
+        name,
+        input,
+        volume,
+    } = audio_source().await?;
+    let track = Track::new_with_data(input, Arc::new(name)).volume(volume);
+    let handle = {
+        let mut call = call_lock.lock().await;
+        match mode {
+            SfxMode::Overlap => call.play(track),
+            SfxMode::Queue => call.enqueue(track).await,
+        }
+    };
 
-    let call_lock = util::join_or_get_call(ctx, guild_id, ctx.author().id).await?;
-    let audio = audio_source().await?;
-    call_lock.lock().await.play_input(audio);
+    if let Err(e) = handle.add_event(
+        Event::Track(TrackEvent::End),
+        ResetIdleTimer {
+            ctx: ctx.clone(),
+            guild_id,
+        },
+    ) {
+        tracing::error!("Failed to watch for the end of a track: {e:?}");
+    }
+    schedule_leave(ctx, bot, guild_id).await?;
 
-    let data = ctx.data();
-    let mut dm = data.daemons.lock().await;
+    Ok(handle)
+}
+
+/// Schedules leaving the guild's call once it's been idle for long enough.
+pub(crate) async fn schedule_leave(
+    ctx: &serenity::all::Context,
+    bot: &crate::Bot,
+    guild_id: GuildId,
+) -> anyhow::Result<()> {
+    let timeout = prefs::guild::get(guild_id)
+        .await?
+        .unwrap_or_default()
+        .idle_timeout();
+    let mut leave_voice = bot.leave_voice.lock().await;
+    let mut dm = bot.daemons.lock().await;
+    let Some(timeout) = timeout else {
+        leave_voice.remove(&mut dm, guild_id).await;
+        return Ok(());
+    };
+    let when = Utc::now() + timeout;
     let id = dm
         .add_daemon(LeaveVoice {
-            when: Utc::now()
-                .checked_add_signed(Duration::minutes(30))
-                .unwrap(),
+            when,
             guild_id,
-            songbird: ctx
-                .serenity_context()
-                .data
-                .read()
+            songbird: songbird::get(ctx)
                 .await
-                .get::<songbird::SongbirdKey>()
-                .unwrap()
-                .clone(),
+                .context("Songbird not initialized")?,
         })
         .await;
+    leave_voice.set(&mut dm, guild_id, id, when).await;
+    Ok(())
+}
 
-    data.leave_voice
-        .lock()
-        .await
-        .set(&mut dm, guild_id, id)
-        .await;
+/// Restarts the idle timer when a track finishes.
+struct ResetIdleTimer {
+    ctx: serenity::all::Context,
+    guild_id: GuildId,
+}
 
-    Ok(())
+#[serenity::async_trait]
+impl songbird::EventHandler for ResetIdleTimer {
+    async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
+        // the call was left, which also ends its tracks
+        songbird::get(&self.ctx).await?.get(self.guild_id)?;
+        let bot = self.ctx.data.read().await.get::<crate::Bot>().cloned()?;
+        if let Err(e) = schedule_leave(&self.ctx, &bot, self.guild_id).await {
+            tracing::error!("Failed to reschedule leaving voice: {e:#}");
+        }
+        None
+    }
 }
 
-async fn find_file(search_string: &str) -> io::Result<PathBuf> {
-    use std::io::{Error, ErrorKind::NotFound};
-    let (search, vec) = fs::read_dir(sfx_path::<&str, _>(None).await?)?
-        .filter_map(Result::ok)
+/// Finds the sfx whose name (with or without extension) or alias is exactly `name`.
+async fn find_exact(guild_id: GuildId, name: &str) -> io::Result<Option<PathBuf>> {
+    let metadata = metadata::all().await?;
+    for library in Library::visible_from(guild_id).await? {
+        for file in library.files()? {
+            let path = file.path();
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            if normalize_label(&file_name) == name
+                || normalize_label(&stem) == name
+                || metadata
+                    .get(&*path.to_string_lossy())
+                    .is_some_and(|m| m.aliases.contains(name))
+            {
+                return Ok(Some(path));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Searches the sfx by their names, aliases and tags, best matches first.
+async fn search(guild_id: GuildId, search_string: &str) -> io::Result<Vec<PathBuf>> {
+    let metadata = metadata::all().await?;
+    let mut files = Vec::new();
+    for library in Library::visible_from(guild_id).await? {
+        files.extend(library.files()?.map(|e| e.path()));
+    }
+    let search = files
+        .iter()
         .enumerate()
-        .fold(
-            (SimSearch::new(), Vec::new()),
-            |(mut search, mut vec), (id, name)| {
-                vec.push(name);
-                search.insert(id, vec[vec.len() - 1].file_name().to_str().unwrap());
-                (search, vec)
-            },
-        );
-    match search.search(search_string).first() {
-        Some(&i) => Ok(vec[i].path()),
-        None => Err(Error::new(
-            NotFound,
-            format!("No matches for {}", search_string),
-        )),
+        .fold(SimSearch::new(), |mut search, (id, file)| {
+            let mut terms = vec![file.file_name().unwrap().to_string_lossy().into_owned()];
+            if let Some(meta) = metadata.get(&*file.to_string_lossy()) {
+                terms.extend(meta.aliases.iter().cloned());
+                terms.extend(meta.tags.iter().cloned());
+            }
+            search.insert(id, &terms.join(" "));
+            search
+        });
+    Ok(search
+        .search(search_string)
+        .into_iter()
+        .map(|i| files[i].clone())
+        .collect())
+}
+
+/// Finds an sfx by its name, aliases or tags, falling back to a fuzzy match.
+pub(crate) async fn find_file(guild_id: GuildId, search_string: &str) -> io::Result<PathBuf> {
+    use std::io::{Error, ErrorKind::NotFound};
+    if let Some(file) = find_exact(guild_id, &normalize_label(search_string)).await? {
+        return Ok(file);
     }
+    search(guild_id, search_string)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::new(NotFound, format!("No matches for {}", search_string)))
 }
 
-async fn sfx_path<S: AsRef<str>, F: Into<Option<S>>>(file: F) -> io::Result<PathBuf> {
-    let p: PathBuf = match file.into() {
-        Some(f) => [SFX_FILES_DIR, f.as_ref()].iter().collect(),
-        None => [SFX_FILES_DIR].iter().collect(),
+pub(crate) async fn autocomplete_sfx(
+    ctx: super::Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let matches = match ctx.guild_id() {
+        Some(guild_id) if partial.is_empty() => all_files(guild_id).await,
+        Some(guild_id) => search(guild_id, partial).await,
+        None => Ok(Vec::new()),
     };
-    DirBuilder::new()
-        .recursive(true)
-        .create(if p.components().count() > 2 {
-            p.parent().unwrap()
-        } else {
-            p.as_path()
-        })?;
-    Ok(p)
+    matches
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to autocomplete sfx: {}", e);
+            Vec::new()
+        })
+        .into_iter()
+        .take(25)
+        .map(|file| {
+            let name = file.file_name().unwrap().to_string_lossy().into_owned();
+            let label = match Library::of(&file) {
+                Some(Library::Shared) => format!("{name} (shared)"),
+                _ => name.clone(),
+            };
+            AutocompleteChoice::new(label, name)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Every sfx the guild can play with a tag, or all of them if there's no tag, sorted by name.
+async fn tagged_files(guild_id: GuildId, tag: Option<&str>) -> io::Result<Vec<PathBuf>> {
+    let mut files = all_files(guild_id).await?;
+    if let Some(tag) = tag {
+        let metadata = metadata::all().await?;
+        files.retain(|f| {
+            metadata
+                .get(&*f.to_string_lossy())
+                .is_some_and(|m| m.tags.contains(tag))
+        });
+    }
+    Ok(files)
+}
+
+/// Every sfx the guild can play, sorted by name.
+async fn all_files(guild_id: GuildId) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for library in Library::visible_from(guild_id).await? {
+        files.extend(library.files()?.map(|e| e.path()));
+    }
+    files.sort_by_cached_key(|f| {
+        unicase::UniCase::new(f.file_name().unwrap().to_string_lossy().into_owned())
+    });
+    Ok(files)
 }
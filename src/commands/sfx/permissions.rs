@@ -0,0 +1,191 @@
+use crate::prefs::{self, guild::SfxPermissions};
+use anyhow::Context as _;
+use itertools::Itertools;
+use poise::command;
+use serenity::all::{Cache, GuildId, Member, Mentionable as _, RoleId, UserId};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Action {
+    Upload,
+    Delete,
+    Play,
+}
+
+impl Action {
+    fn roles(self, permissions: &mut SfxPermissions) -> &mut BTreeSet<RoleId> {
+        match self {
+            Self::Upload => &mut permissions.upload_roles,
+            Self::Delete => &mut permissions.delete_roles,
+            Self::Play => &mut permissions.play_roles,
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Delete => "delete",
+            Self::Play => "play",
+        }
+    }
+}
+
+static LAST_PLAYED: LazyLock<Mutex<HashMap<(GuildId, UserId), Instant>>> =
+    LazyLock::new(Default::default);
+
+/// Whether a member is the owner of the guild or has a role with administrator permissions.
+pub(super) fn is_admin(cache: &Cache, guild_id: GuildId, member: &Member) -> bool {
+    let Some(guild) = cache.guild(guild_id) else {
+        return false;
+    };
+    guild.owner_id == member.user.id
+        || member
+            .roles
+            .iter()
+            .chain([&guild_id.everyone_role()])
+            .filter_map(|r| guild.roles.get(r))
+            .any(|r| r.permissions.administrator())
+}
+
+/// Fails unless the member has a role the guild requires for this action.
+pub(super) async fn ensure_allowed(
+    cache: &Cache,
+    guild_id: GuildId,
+    member: &Member,
+    action: Action,
+) -> anyhow::Result<()> {
+    let mut permissions = prefs::guild::get(guild_id)
+        .await?
+        .unwrap_or_default()
+        .sfx_permissions;
+    let roles = action.roles(&mut permissions);
+    if roles.is_empty()
+        || member.roles.iter().any(|r| roles.contains(r))
+        || is_admin(cache, guild_id, member)
+    {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "You need to be {} to {} sfx",
+        roles.iter().map(|r| r.mention()).format(" or "),
+        action.verb(),
+    ))
+}
+
+/// Fails if the member isn't allowed to play sfx or has played one too recently.
+pub(super) async fn ensure_can_play(
+    cache: &Cache,
+    guild_id: GuildId,
+    member: &Member,
+) -> anyhow::Result<()> {
+    ensure_allowed(cache, guild_id, member, Action::Play).await?;
+    let cooldown = prefs::guild::get(guild_id)
+        .await?
+        .and_then(|p| p.sfx_permissions.play_cooldown)
+        .map(Duration::from_secs);
+    let Some(cooldown) = cooldown.filter(|_| !is_admin(cache, guild_id, member)) else {
+        return Ok(());
+    };
+    if let Some(at) = LAST_PLAYED.lock().unwrap().get(&(guild_id, member.user.id))
+        && let Some(left) = cooldown.checked_sub(at.elapsed())
+        && !left.is_zero()
+    {
+        anyhow::bail!(
+            "Slow down! You can play another sfx in {}s",
+            left.as_secs() + 1
+        );
+    }
+    Ok(())
+}
+
+/// Starts the member's cooldown, once they've played an sfx.
+pub(super) fn start_cooldown(guild_id: GuildId, user: UserId) {
+    LAST_PLAYED
+        .lock()
+        .unwrap()
+        .insert((guild_id, user), Instant::now());
+}
+
+async fn check(ctx: super::super::Context<'_>, action: Action) -> anyhow::Result<bool> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let member = ctx.author_member().await.context("Not in a guild")?;
+    match action {
+        Action::Play => ensure_can_play(ctx.cache(), guild_id, &member).await?,
+        action => ensure_allowed(ctx.cache(), guild_id, &member, action).await?,
+    }
+    Ok(true)
+}
+
+pub(super) async fn can_upload(ctx: super::super::Context<'_>) -> anyhow::Result<bool> {
+    check(ctx, Action::Upload).await
+}
+
+pub(super) async fn can_delete(ctx: super::super::Context<'_>) -> anyhow::Result<bool> {
+    check(ctx, Action::Delete).await
+}
+
+pub(super) async fn can_play(ctx: super::super::Context<'_>) -> anyhow::Result<bool> {
+    check(ctx, Action::Play).await
+}
+
+/// Allow or stop a role from uploading, deleting or playing sfx
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub(super) async fn roles(
+    ctx: super::super::Context<'_>,
+    action: Action,
+    role: RoleId,
+    allowed: bool,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let roles = prefs::guild::update(guild_id, |p| {
+        let roles = action.roles(&mut p.sfx_permissions);
+        if allowed {
+            roles.insert(role);
+        } else {
+            roles.remove(&role);
+        }
+        roles.clone()
+    })
+    .await?;
+    ctx.say(if roles.is_empty() {
+        format!("Everyone can {} sfx", action.verb())
+    } else {
+        format!(
+            "Only {} can {} sfx",
+            roles.iter().map(|r| r.mention()).format(", "),
+            action.verb()
+        )
+    })
+    .await?;
+    Ok(())
+}
+
+/// Make members wait between playing sfx. Leave it empty to remove the cooldown.
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub(super) async fn cooldown(
+    ctx: super::super::Context<'_>,
+    #[description = "Seconds to wait between sfx"] seconds: Option<u64>,
+) -> anyhow::Result<()> {
+    prefs::guild::update(ctx.guild_id().context("Not in a guild")?, |p| {
+        p.sfx_permissions.play_cooldown = seconds.filter(|s| *s > 0)
+    })
+    .await?;
+    ctx.say(match seconds {
+        Some(s) if s > 0 => format!("Members have to wait {s}s between sfx"),
+        _ => "Members can play sfx as often as they like".to_owned(),
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use super::stats::SFX_STATS;
+use anyhow::Context as _;
+use poise::command;
+use rand::distr::{Distribution as _, weighted::WeightedIndex};
+use serenity::all::GuildId;
+use std::path::PathBuf;
+
+/// How likely each sfx is to be picked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Weighting {
+    /// Every sfx is as likely.
+    #[default]
+    Uniform,
+    /// The more an sfx has been played the more likely it is.
+    Popular,
+    /// The less an sfx has been played the more likely it is.
+    Forgotten,
+}
+
+/// Picks one of the guild's sfx, optionally only among those with a tag.
+pub(super) async fn choose(
+    guild_id: GuildId,
+    tag: Option<&str>,
+    weighting: Weighting,
+) -> anyhow::Result<PathBuf> {
+    let mut files = super::tagged_files(guild_id, tag).await?;
+    let weights = {
+        let stats = SFX_STATS.load().await?;
+        files
+            .iter()
+            .map(|f| {
+                let plays = stats.0.get(&*f.to_string_lossy()).copied().unwrap_or(0) as f64;
+                match weighting {
+                    Weighting::Uniform => 1.0,
+                    Weighting::Popular => plays + 1.0,
+                    Weighting::Forgotten => 1.0 / (plays + 1.0),
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let index = WeightedIndex::new(&weights).map_err(|_| match tag {
+        Some(tag) => anyhow::anyhow!("No sfx tagged {tag}"),
+        None => anyhow::anyhow!("No sfx to pick from"),
+    })?;
+    Ok(files.swap_remove(index.sample(&mut rand::rng())))
+}
+
+/// Play a random sfx
+#[command(slash_command, guild_only, check = "super::can_play")]
+pub(super) async fn random(
+    ctx: super::super::Context<'_>,
+    #[description = "Only pick sfx with this tag"] tag: Option<String>,
+    #[description = "Favour the most or the least played sfx"] weighting: Option<Weighting>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let tag = tag.map(|t| super::normalize_label(&t));
+    let file = choose(guild_id, tag.as_deref(), weighting.unwrap_or_default()).await?;
+    super::play_clip(
+        ctx.serenity_context(),
+        ctx.data(),
+        guild_id,
+        ctx.author().id,
+        &file,
+    )
+    .await?;
+    super::permissions::start_cooldown(guild_id, ctx.author().id);
+    ctx.say(format!(
+        "**Playing:** {}",
+        file.file_name().unwrap().display()
+    ))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use super::{metadata::key, util::paginate};
+use crate::in_files;
+use anyhow::Context as _;
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use json_db::GlobalDatabase;
+use poise::command;
+use serde::{Deserialize, Serialize};
+use serenity::all::{CreateEmbed, GuildId, Mentionable as _, UserId};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SfxStats(pub(super) HashMap<String, usize>);
+
+impl SfxStats {
+    fn update(&mut self, sfx: &str) {
+        self.0
+            .entry(sfx.to_string())
+            .and_modify(|c| *c += 1)
+            .or_insert(1);
+    }
+}
+
+/// Total plays of each sfx in every guild, used to favour popular sfx when picking at random.
+pub(super) static SFX_STATS: GlobalDatabase<SfxStats> =
+    GlobalDatabase::new(in_files!("sfx_stats.json"));
+
+/// Plays from the last [`KEEP_PLAYS_DAYS`] days, oldest first.
+static PLAYS: GlobalDatabase<Vec<Play>> = GlobalDatabase::new(in_files!("sfx_plays.json"));
+
+/// Totals of each guild's plays that are too old to be kept individually.
+static OLD_PLAYS: GlobalDatabase<HashMap<GuildId, PlayTotals>> =
+    GlobalDatabase::new(in_files!("sfx_old_plays.json"));
+
+/// As long as the longest period besides all time.
+const KEEP_PLAYS_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Play {
+    clip: String,
+    user: UserId,
+    guild: GuildId,
+    at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct PlayTotals {
+    clips: HashMap<String, usize>,
+    users: HashMap<UserId, usize>,
+}
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum StatsView {
+    #[name = "Most played sfx"]
+    Clip,
+    #[name = "Who plays the most sfx"]
+    User,
+}
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum StatsPeriod {
+    #[name = "Last 7 days"]
+    Week,
+    #[name = "Last 30 days"]
+    Month,
+    #[name = "All time"]
+    All,
+}
+
+impl StatsPeriod {
+    pub fn since(self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Week => Some(Utc::now() - Duration::days(7)),
+            Self::Month => Some(Utc::now() - Duration::days(KEEP_PLAYS_DAYS)),
+            Self::All => None,
+        }
+    }
+}
+
+pub(super) async fn record(clip: &Path, guild: GuildId, user: UserId) -> io::Result<()> {
+    let clip = key(clip);
+    SFX_STATS.load().await?.update(&clip);
+    let mut plays = PLAYS.load().await?;
+    let now = Utc::now();
+    plays.push(Play {
+        clip,
+        user,
+        guild,
+        at: now,
+    });
+    let old = plays.partition_point(|p| p.at < now - Duration::days(KEEP_PLAYS_DAYS));
+    if old > 0 {
+        let mut totals = OLD_PLAYS.load().await?;
+        for play in plays.drain(..old) {
+            let totals = totals.entry(play.guild).or_default();
+            *totals.clips.entry(play.clip).or_default() += 1;
+            *totals.users.entry(play.user).or_default() += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the stats of a clip that was renamed.
+pub(super) async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    let (from, to) = (key(from), key(to));
+    {
+        let mut stats = SFX_STATS.load().await?;
+        if let Some(count) = stats.0.remove(&from) {
+            *stats.0.entry(to.clone()).or_default() += count;
+        }
+    }
+    for play in PLAYS.load().await?.iter_mut().filter(|p| p.clip == from) {
+        play.clip = to.clone();
+    }
+    for totals in OLD_PLAYS.load().await?.values_mut() {
+        if let Some(count) = totals.clips.remove(&from) {
+            *totals.clips.entry(to.clone()).or_default() += count;
+        }
+    }
+    Ok(())
+}
+
+/// Carries the plays of sfx from before libraries existed over to the guild they were moved to.
+pub(super) async fn migrate(guild: GuildId, moved: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    let mut stats = SFX_STATS.load().await?;
+    let mut old_plays = OLD_PLAYS.load().await?;
+    let totals = old_plays.entry(guild).or_default();
+    for (from, to) in moved {
+        if let Some(count) = stats.0.remove(&key(from)) {
+            *stats.0.entry(key(to)).or_default() += count;
+            *totals.clips.entry(key(to)).or_default() += count;
+        }
+    }
+    Ok(())
+}
+
+/// Show how often sfx are played
+#[command(slash_command, guild_only)]
+pub(super) async fn stats(
+    ctx: super::super::Context<'_>,
+    #[description = "What to rank, defaults to sfx"] by: Option<StatsView>,
+    #[description = "Which plays to count, defaults to all of them"] period: Option<StatsPeriod>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let by = by.unwrap_or(StatsView::Clip);
+    let period = period.unwrap_or(StatsPeriod::All);
+    let since = period.since();
+    let mut counts = PLAYS
+        .load()
+        .await?
+        .iter()
+        .filter(|p| p.guild == guild_id && since.is_none_or(|since| p.at >= since))
+        .counts_by(|p| match by {
+            StatsView::Clip => clip_name(&p.clip),
+            StatsView::User => p.user.mention().to_string(),
+        });
+    if since.is_none()
+        && let Some(totals) = OLD_PLAYS.load().await?.get(&guild_id)
+    {
+        match by {
+            StatsView::Clip => {
+                for (clip, count) in &totals.clips {
+                    *counts.entry(clip_name(clip)).or_default() += count;
+                }
+            }
+            StatsView::User => {
+                for (user, count) in &totals.users {
+                    *counts.entry(user.mention().to_string()).or_default() += count;
+                }
+            }
+        }
+    }
+    let title = format!(
+        "{} ({})",
+        match by {
+            StatsView::Clip => "Most played sfx",
+            StatsView::User => "Who plays the most sfx",
+        },
+        match period {
+            StatsPeriod::Week => "last 7 days",
+            StatsPeriod::Month => "last 30 days",
+            StatsPeriod::All => "all time",
+        }
+    );
+    let pages = counts
+        .into_iter()
+        .sorted_unstable_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)))
+        .enumerate()
+        .chunks(20)
+        .into_iter()
+        .map(|page| {
+            CreateEmbed::new().title(&title).description(
+                page.format_with("\n", |(rank, (name, count)), f| {
+                    f(&format_args!("{}. **{count}** {name}", rank + 1))
+                })
+                .to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    if pages.is_empty() {
+        ctx.say("No sfx have been played yet").await?;
+    } else {
+        paginate(ctx, &pages).await?;
+    }
+    Ok(())
+}
+
+fn clip_name(key: &str) -> String {
+    Path::new(key)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,172 @@
+use super::{
+    library::Library,
+    metadata::{self, ClipMeta},
+};
+use crate::in_files;
+use chrono::{DateTime, Duration, Utc};
+use daemons::{ControlFlow, Daemon};
+use json_db::GlobalDatabase;
+use serde::{Deserialize, Serialize};
+use serenity::all::{GuildId, Http, UserId};
+use std::{
+    fs::{self, DirBuilder},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration as StdDuration,
+};
+
+const TRASH_DIR: &str = in_files!("sfx_trash");
+/// How many days a deleted sfx can still be restored for.
+const RETENTION_DAYS: i64 = 30;
+
+static TRASH: GlobalDatabase<Vec<Trashed>> = GlobalDatabase::new(in_files!("sfx_trash.json"));
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Trashed {
+    /// Where the sfx was before it was deleted.
+    pub original: PathBuf,
+    /// Where the sfx is kept until it's purged.
+    pub trashed: PathBuf,
+    pub deleted_by: UserId,
+    pub deleted_at: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: ClipMeta,
+}
+
+impl Trashed {
+    pub fn name(&self) -> String {
+        self.original
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Identifies this entry, the name it has in the trash is unique.
+    pub fn id(&self) -> String {
+        self.trashed
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Moves an sfx, along with its metadata, to the trash.
+pub async fn trash(file: &Path, deleted_by: UserId) -> io::Result<Trashed> {
+    trash_as(file, file, deleted_by).await
+}
+
+/// Moves an sfx to the trash, to be restored as `restore_to` instead of where it was.
+pub async fn trash_as(file: &Path, restore_to: &Path, deleted_by: UserId) -> io::Result<Trashed> {
+    DirBuilder::new().recursive(true).create(TRASH_DIR)?;
+    let deleted_at = Utc::now();
+    let trashed = Path::new(TRASH_DIR).join(format!(
+        "{}-{}",
+        deleted_at.timestamp_millis(),
+        file.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    fs::rename(file, &trashed)?;
+    let entry = Trashed {
+        original: restore_to.to_owned(),
+        trashed,
+        deleted_by,
+        deleted_at,
+        metadata: metadata::remove(file).await?.unwrap_or_default(),
+    };
+    TRASH.load().await?.push(entry.clone());
+    Ok(entry)
+}
+
+/// The sfx in the trash that the guild could play before they were deleted, newest first.
+pub async fn visible_from(guild_id: GuildId) -> io::Result<Vec<Trashed>> {
+    let libraries = Library::visible_from(guild_id).await?;
+    let mut trash = TRASH
+        .load()
+        .await?
+        .iter()
+        .filter(|t| Library::of(&t.original).is_some_and(|l| libraries.contains(&l)))
+        .cloned()
+        .collect::<Vec<_>>();
+    trash.sort_unstable_by_key(|t| std::cmp::Reverse(t.deleted_at));
+    Ok(trash)
+}
+
+/// Finds a deleted sfx by its id, or the most recently deleted one with that name.
+pub async fn find(guild_id: GuildId, query: &str) -> anyhow::Result<Trashed> {
+    let trash = visible_from(guild_id).await?;
+    let name = super::normalize_label(query);
+    trash
+        .iter()
+        .find(|t| t.id() == query)
+        .or_else(|| {
+            trash.iter().find(|t| {
+                super::normalize_label(&t.name()) == name
+                    || t.original
+                        .file_stem()
+                        .is_some_and(|s| super::normalize_label(&s.to_string_lossy()) == name)
+            })
+        })
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No deleted sfx called {query}"))
+}
+
+/// Puts a deleted sfx back where it was.
+pub async fn restore(entry: &Trashed) -> anyhow::Result<()> {
+    if entry.original.exists() {
+        anyhow::bail!(
+            "There's already an sfx called {}, rename it before restoring this one",
+            entry.name()
+        );
+    }
+    if let Some(dir) = entry.original.parent() {
+        DirBuilder::new().recursive(true).create(dir)?;
+    }
+    fs::rename(&entry.trashed, &entry.original)?;
+    metadata::update(&entry.original, |m| *m = entry.metadata.clone()).await?;
+    TRASH.load().await?.retain(|t| t.trashed != entry.trashed);
+    Ok(())
+}
+
+/// Permanently deletes what has been in the trash for too long.
+pub struct TrashPurger;
+
+#[serenity::async_trait]
+impl Daemon<true> for TrashPurger {
+    type Data = (Arc<serenity::cache::Cache>, Arc<Http>);
+
+    async fn run(&mut self, _: &Self::Data) -> ControlFlow {
+        let mut trash = match TRASH.load().await {
+            Ok(t) => t,
+            Err(e) => {
+                tracing::error!("failed to load the sfx trash: {e:?}");
+                return ControlFlow::Continue(());
+            }
+        };
+        let cutoff = Utc::now() - Duration::days(RETENTION_DAYS);
+        trash.retain(|t| {
+            if t.deleted_at > cutoff {
+                return true;
+            }
+            tracing::info!("purging {:?} from the sfx trash", t.original);
+            match fs::remove_file(&t.trashed) {
+                Ok(()) => false,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    tracing::error!("failed to purge {:?}: {e:?}", t.trashed);
+                    true
+                }
+            }
+        });
+        ControlFlow::Continue(())
+    }
+
+    async fn interval(&self) -> StdDuration {
+        StdDuration::from_secs(60 * 60)
+    }
+
+    async fn name(&self) -> String {
+        stringify!(TrashPurger).to_string()
+    }
+}
@@ -1,9 +1,14 @@
 use crate::{EVENT_BUS, util::daemons::DaemonManager};
 use anyhow::Context as _;
+use chrono::{DateTime, Utc};
 use futures::prelude::*;
+use mappable_rc::Marc;
 use pubsub::{self, events::VoiceStateUpdate};
 use serenity::{
-    all::Context,
+    all::{
+        ComponentInteraction, Context, CreateActionRow, CreateButton, CreateInteractionResponse,
+        CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    },
     model::id::{ChannelId, GuildId, UserId},
     prelude::TypeMapKey,
 };
@@ -11,20 +16,51 @@ use songbird::Call;
 use std::{collections::HashMap, ops::ControlFlow, sync::Arc};
 use tokio::sync::{Mutex, OnceCell};
 
+/// Discord allows 5 rows of 5 buttons, the last row is kept for turning pages.
+pub const BUTTONS_PER_PAGE: usize = 20;
+
+/// The bot, for when there's only serenity's context.
+pub async fn bot(ctx: &Context) -> anyhow::Result<Marc<crate::Bot>> {
+    ctx.data
+        .read()
+        .await
+        .get::<crate::Bot>()
+        .cloned()
+        .context("Bot not initialized")
+}
+
+/// Whether the bot is in a call in another channel, which it shouldn't be dragged away from.
+pub async fn busy_elsewhere(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel: ChannelId,
+) -> anyhow::Result<bool> {
+    let call = songbird::get(ctx)
+        .await
+        .context("Songbird not initialized")?
+        .get(guild_id);
+    Ok(match call {
+        Some(call) => call
+            .lock()
+            .await
+            .current_channel()
+            .is_some_and(|c| c.0.get() != channel.get()),
+        None => false,
+    })
+}
+
 pub async fn join_or_get_call(
-    ctx: super::super::Context<'_>,
+    ctx: &Context,
     gid: GuildId,
     author: UserId,
 ) -> anyhow::Result<Arc<Mutex<Call>>> {
-    let sb = songbird::get(ctx.serenity_context())
-        .await
-        .expect("Songbird not initialized");
+    let sb = songbird::get(ctx).await.expect("Songbird not initialized");
 
     let call = match sb.get(gid) {
         Some(call) => call,
         None => {
             let (gid, voice_channel) = {
-                let guild = ctx.cache().guild(gid).context("Invalid guild")?;
+                let guild = ctx.cache.guild(gid).context("Invalid guild")?;
                 let voice_channel = guild
                     .voice_states
                     .get(&author)
@@ -40,31 +76,43 @@ pub async fn join_or_get_call(
     Ok(call)
 }
 
+/// The daemon that will leave each guild's call, and when it will.
 #[derive(Debug, Default)]
-pub struct LeaveVoiceDaemons(HashMap<GuildId, usize>);
+pub struct LeaveVoiceDaemons(HashMap<GuildId, (usize, DateTime<Utc>)>);
 
 impl TypeMapKey for LeaveVoiceDaemons {
     type Value = Arc<Mutex<Self>>;
 }
 
 impl LeaveVoiceDaemons {
-    pub async fn set(&mut self, daemons: &mut DaemonManager, guild_id: GuildId, index: usize) {
-        init_voice_leave().await;
-        if let Some(prev) = self.0.insert(guild_id, index) {
+    pub async fn set(
+        &mut self,
+        daemons: &mut DaemonManager,
+        guild_id: GuildId,
+        index: usize,
+        when: DateTime<Utc>,
+    ) {
+        init_voice_events().await;
+        if let Some((prev, _)) = self.0.insert(guild_id, (index, when)) {
             let _ = daemons.cancel(prev).await;
         }
     }
 
     pub async fn remove(&mut self, daemons: &mut DaemonManager, guild_id: GuildId) {
-        if let Some(prev) = self.0.remove(&guild_id) {
+        if let Some((prev, _)) = self.0.remove(&guild_id) {
             let _ = daemons.cancel(prev).await;
         }
     }
+
+    pub fn leaving_at(&self, guild_id: GuildId) -> Option<DateTime<Utc>> {
+        self.0.get(&guild_id).map(|(_, when)| *when)
+    }
 }
 
-async fn init_voice_leave() {
-    static INIT_VOICE_LEAVE: OnceCell<()> = OnceCell::const_new();
-    INIT_VOICE_LEAVE
+/// Leaves calls once only bots are left and plays intros.
+pub(super) async fn init_voice_events() {
+    static INIT_VOICE_EVENTS: OnceCell<()> = OnceCell::const_new();
+    INIT_VOICE_EVENTS
         .get_or_init(|| async {
             EVENT_BUS
                 .subscribe::<VoiceStateUpdate, _>(|ctx, VoiceStateUpdate { old, new }| {
@@ -75,6 +123,9 @@ async fn init_voice_leave() {
                             Some(members.iter().all(|m| m.user.bot))
                         }
                         let ctx = &ctx.serenity;
+                        if let Err(e) = super::intro::greet(ctx, old.as_ref(), new).await {
+                            tracing::error!("Failed to play intro: {e:#}");
+                        }
                         if let Some(id) = old.as_ref().and_then(|vs| vs.channel_id)
                             && alone(id, ctx).await == Some(true)
                             && let Some(guild_id) = new.guild_id
@@ -162,3 +213,90 @@ pub async fn paginate<U, E>(
 
     Ok(())
 }
+
+/// The page of `items` to show, which page that is and how many there are.
+pub fn button_page<T>(items: &[T], page: usize) -> (usize, usize, &[T]) {
+    let pages = items.len().div_ceil(BUTTONS_PER_PAGE).max(1);
+    let page = page % pages;
+    let start = page * BUTTONS_PER_PAGE;
+    (
+        page,
+        pages,
+        &items[start..items.len().min(start + BUTTONS_PER_PAGE)],
+    )
+}
+
+/// Buttons to turn to the previous and next page, if there's more than one.
+pub fn page_buttons(
+    page: usize,
+    pages: usize,
+    id: impl Fn(usize, &str) -> anyhow::Result<String>,
+) -> anyhow::Result<Vec<CreateButton>> {
+    if pages <= 1 {
+        return Ok(Vec::new());
+    }
+    Ok(vec![
+        CreateButton::new(id((page + pages - 1) % pages, "prev")?).emoji('◀'),
+        CreateButton::new(id((page + 1) % pages, "next")?).emoji('▶'),
+    ])
+}
+
+/// Lays buttons out in rows of 5, with the controls in a row of their own.
+pub fn button_rows(
+    buttons: Vec<CreateButton>,
+    controls: Vec<CreateButton>,
+) -> Vec<CreateActionRow> {
+    let mut rows = buttons
+        .chunks(5)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect::<Vec<_>>();
+    if !controls.is_empty() {
+        rows.push(CreateActionRow::Buttons(controls));
+    }
+    rows
+}
+
+/// Acknowledges a button press, telling only whoever pressed it if it fails.
+pub async fn acknowledge_press<F>(ctx: &Context, press: &ComponentInteraction, action: F)
+where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    if let Err(e) = press
+        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+        .await
+    {
+        tracing::error!("failed to acknowledge button press: {e:?}");
+        return;
+    }
+    if let Err(e) = action.await {
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(format!("{e:#}"))
+            .ephemeral(true);
+        if let Err(e) = press.create_followup(ctx, followup).await {
+            tracing::error!("failed to report button press error: {e:?}");
+        }
+    }
+}
+
+/// Replaces the pressed message with another page, or tells whoever pressed it why it can't.
+pub async fn turn_page(
+    ctx: &Context,
+    press: &ComponentInteraction,
+    page: anyhow::Result<(String, Vec<CreateActionRow>)>,
+) {
+    let response = match page {
+        Ok((content, components)) => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(components),
+        ),
+        Err(e) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(format!("{e:#}"))
+                .ephemeral(true),
+        ),
+    };
+    if let Err(e) = press.create_response(ctx, response).await {
+        tracing::error!("failed to turn page: {e:?}");
+    }
+}
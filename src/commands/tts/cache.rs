@@ -0,0 +1,115 @@
+use super::provider::{TtsProvider, Voice};
+use crate::in_files;
+use sha1::{Digest as _, Sha1};
+use std::{
+    fs::{self, DirBuilder, File},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+const CACHE_DIR: &str = in_files!("tts_cache");
+const MAX_CACHE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Named by a digest of what's spoken, which stays the same across builds unlike std's hashers.
+fn file_name(voice: &Voice, text: &str, extension: &str) -> String {
+    let mut hasher = Sha1::new();
+    for part in [voice.service, voice.name, text] {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+    format!("{:x}.{extension}", hasher.finalize())
+}
+
+/// The file with the text spoken in the voice, generating it if it isn't cached.
+pub(super) async fn audio(
+    provider: &dyn TtsProvider,
+    voice: &Voice,
+    text: &str,
+) -> anyhow::Result<PathBuf> {
+    let path = Path::new(CACHE_DIR).join(file_name(voice, text, provider.extension()));
+    match File::options().write(true).open(&path) {
+        Ok(file) => {
+            file.set_modified(SystemTime::now())?;
+            return Ok(path);
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let audio = provider.generate(voice.service, voice.name, text).await?;
+    DirBuilder::new().recursive(true).create(CACHE_DIR)?;
+    // written somewhere else first so a half written file is never played
+    let mut file = tempfile::NamedTempFile::new_in(CACHE_DIR)?;
+    file.write_all(&audio)?;
+    file.persist(&path)?;
+    if let Err(e) = evict(Path::new(CACHE_DIR), MAX_CACHE_SIZE) {
+        tracing::error!("failed to evict from the tts cache: {e:?}");
+    }
+    Ok(path)
+}
+
+/// Deletes the least recently used files until the ones left fit in `max_size`.
+fn evict(dir: &Path, max_size: u64) -> io::Result<()> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // still being written
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        files.push((metadata.modified()?, metadata.len(), entry.path()));
+    }
+    let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+    files.sort_unstable();
+    for (_, len, path) in files {
+        if size <= max_size {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => size -= len,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => size -= len,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn file_names_are_stable() {
+        let voice = Voice {
+            service: "espeak-ng",
+            name: "en",
+            language: "English",
+        };
+        assert_eq!(
+            file_name(&voice, "hello", "wav"),
+            "a6396ba2fe6bc42ce381c2301594727097604748.wav"
+        );
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("oldest", 30), ("older", 20), ("newest", 10)] {
+            let path = dir.path().join(name);
+            fs::write(&path, [0; 10]).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+        evict(dir.path(), 20).unwrap();
+        assert!(!dir.path().join("oldest").exists());
+        assert!(dir.path().join("older").exists());
+        assert!(dir.path().join("newest").exists());
+    }
+}
@@ -0,0 +1,211 @@
+mod cache;
+mod provider;
+mod reader;
+mod voices;
+
+use crate::prefs::{
+    self,
+    guild::{TtsEngine, TtsVoice},
+};
+use anyhow::Context as _;
+use poise::{ChoiceParameter as _, CreateReply, command};
+use provider::{TtsProvider, Voice};
+use reader::reader;
+use serenity::all::{CreateActionRow, CreateAttachment, GuildId, UserId};
+use songbird::input::{File, Input};
+use std::{fs, io};
+use voices::voices;
+
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("say", "config", "default", "voices", "reader", "download", "engine")
+)]
+pub async fn tts(_: super::Context<'_>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+pub async fn initialize(events: &pubsub::EventBus) {
+    voices::initialize(events).await;
+    reader::initialize(events).await;
+}
+
+/// play a tts message over voice
+#[command(slash_command, guild_only)]
+pub async fn say(ctx: super::Context<'_>, text: String) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let user = ctx.author().id;
+    super::sfx::play_sfx(ctx, || async {
+        Ok(super::sfx::Sound {
+            name: format!("tts: {text}"),
+            input: speak(guild_id, user, &text).await?,
+            volume: 1.0,
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// The engine and voice someone's tts is spoken with.
+async fn voice_of(
+    guild_id: Option<GuildId>,
+    user: UserId,
+) -> anyhow::Result<(TtsEngine, &'static Voice)> {
+    let guild_prefs = match guild_id {
+        Some(guild_id) => prefs::guild::get(guild_id).await?.unwrap_or_default(),
+        None => Default::default(),
+    };
+    let user_voice = prefs::user::get(user).await?.and_then(|p| p.tts_voice);
+    let provider = guild_prefs.tts_engine.provider();
+    let voice = [user_voice, guild_prefs.tts_voice]
+        .into_iter()
+        .flatten()
+        .find_map(|v| provider.find_voice(Some(&v.service), &v.voice).ok())
+        .unwrap_or_else(|| provider.default_voice());
+    Ok((guild_prefs.tts_engine, voice))
+}
+
+/// Speaks the text with the guild's engine, in the user's voice.
+async fn speak(guild_id: GuildId, user: UserId, text: &str) -> anyhow::Result<Input> {
+    let (engine, voice) = voice_of(Some(guild_id), user).await?;
+    speak_with(engine.provider(), voice, text).await
+}
+
+async fn speak_with(
+    provider: &dyn TtsProvider,
+    voice: &Voice,
+    text: &str,
+) -> anyhow::Result<Input> {
+    Ok(File::new(cache::audio(provider, voice, text).await?).into())
+}
+
+async fn engine_of(guild_id: Option<GuildId>) -> io::Result<TtsEngine> {
+    Ok(match guild_id {
+        Some(guild_id) => {
+            prefs::guild::get(guild_id)
+                .await?
+                .unwrap_or_default()
+                .tts_engine
+        }
+        None => TtsEngine::default(),
+    })
+}
+
+/// Looks a voice up in the guild's engine, so only voices it has get saved.
+async fn validate(
+    guild_id: Option<GuildId>,
+    service: Option<String>,
+    voice: &str,
+) -> anyhow::Result<&'static Voice> {
+    engine_of(guild_id)
+        .await?
+        .provider()
+        .find_voice(service.as_deref(), voice)
+}
+
+/// Says which voice was chosen, with a button to hear it.
+fn chosen(content: String, voice: Option<&Voice>) -> CreateReply {
+    let reply = CreateReply::default().content(content);
+    match voice {
+        Some(voice) => reply.components(vec![CreateActionRow::Buttons(vec![
+            voices::preview_button(voice),
+        ])]),
+        None => reply,
+    }
+}
+
+/// choose your tts voice, leave it empty to use the server's
+#[command(slash_command)]
+pub async fn config(
+    ctx: super::Context<'_>,
+    #[description = "Which voice to speak with"]
+    #[autocomplete = "voices::autocomplete_voice"]
+    voice: Option<String>,
+    #[description = "The service the voice is from"]
+    #[autocomplete = "voices::autocomplete_service"]
+    service: Option<String>,
+) -> anyhow::Result<()> {
+    let voice = match voice {
+        Some(voice) => Some(validate(ctx.guild_id(), service, &voice).await?),
+        None => None,
+    };
+    prefs::user::update(ctx.author().id, |p| {
+        p.tts_voice = voice.map(|v| TtsVoice {
+            service: v.service.to_owned(),
+            voice: v.name.to_owned(),
+        })
+    })
+    .await?;
+    let content = match voice {
+        Some(v) => format!("You'll now speak as {} from {}", v.name, v.service),
+        None => "You'll now speak with the server's voice".to_owned(),
+    };
+    ctx.send(chosen(content, voice.filter(|_| ctx.guild_id().is_some())))
+        .await?;
+    Ok(())
+}
+
+/// choose the server's tts voice, leave it empty to use the engine's default
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub async fn default(
+    ctx: super::Context<'_>,
+    #[description = "Which voice to speak with"]
+    #[autocomplete = "voices::autocomplete_voice"]
+    voice: Option<String>,
+    #[description = "The service the voice is from"]
+    #[autocomplete = "voices::autocomplete_service"]
+    service: Option<String>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let voice = match voice {
+        Some(voice) => Some(validate(Some(guild_id), service, &voice).await?),
+        None => None,
+    };
+    prefs::guild::update(guild_id, |p| {
+        p.tts_voice = voice.map(|v| TtsVoice {
+            service: v.service.to_owned(),
+            voice: v.name.to_owned(),
+        })
+    })
+    .await?;
+    let content = match voice {
+        Some(v) => format!("The server will now speak as {} from {}", v.name, v.service),
+        None => "The server will now speak with the engine's default voice".to_owned(),
+    };
+    ctx.send(chosen(content, voice)).await?;
+    Ok(())
+}
+
+/// choose what generates this server's tts
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub async fn engine(ctx: super::Context<'_>, engine: TtsEngine) -> anyhow::Result<()> {
+    prefs::guild::update(ctx.guild_id().context("Not in a guild")?, |p| {
+        p.tts_engine = engine
+    })
+    .await?;
+    ctx.say(format!("Tts will now be generated by {}", engine.name()))
+        .await?;
+    Ok(())
+}
+
+/// generates a tss audio file
+#[command(slash_command)]
+pub async fn download(ctx: super::Context<'_>, text: String) -> anyhow::Result<()> {
+    let (engine, voice) = voice_of(ctx.guild_id(), ctx.author().id).await?;
+    let provider = engine.provider();
+    let file = cache::audio(provider, voice, &text).await?;
+    ctx.send(CreateReply::default().attachment(CreateAttachment::bytes(
+        fs::read(file)?,
+        format!("{}.{}", voice.name, provider.extension()),
+    )))
+    .await?;
+    Ok(())
+}
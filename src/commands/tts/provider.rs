@@ -0,0 +1,238 @@
+use crate::prefs::guild::TtsEngine;
+use anyhow::Context as _;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{process::Stdio, sync::OnceLock};
+use tokio::process::Command;
+
+/// A voice one of the engines can speak with.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Voice {
+    pub service: &'static str,
+    pub name: &'static str,
+    pub language: &'static str,
+}
+
+const fn voice(service: &'static str, name: &'static str, language: &'static str) -> Voice {
+    Voice {
+        service,
+        name,
+        language,
+    }
+}
+
+#[serenity::async_trait]
+pub trait TtsProvider: Send + Sync {
+    /// Every voice this engine can speak with, the first one is used when none is configured.
+    fn voices(&self) -> &'static [Voice];
+
+    fn default_voice(&self) -> &'static Voice {
+        &self.voices()[0]
+    }
+
+    /// Finds one of the engine's voices by name or by `service:name`, ignoring case.
+    fn find_voice(&self, service: Option<&str>, name: &str) -> anyhow::Result<&'static Voice> {
+        let (service, name) = match name.split_once(':') {
+            Some((service, name)) => (Some(service), name),
+            None => (service, name),
+        };
+        self.voices()
+            .iter()
+            .find(|v| {
+                v.name.eq_ignore_ascii_case(name)
+                    && service.is_none_or(|s| v.service.eq_ignore_ascii_case(s))
+            })
+            .ok_or_else(|| match service {
+                Some(service) => anyhow::anyhow!("There's no voice called {name} in {service}"),
+                None => anyhow::anyhow!("There's no voice called {name}"),
+            })
+    }
+
+    /// The extension of the audio files this engine generates.
+    fn extension(&self) -> &'static str;
+
+    /// Generates the audio of the text being spoken.
+    async fn generate(&self, service: &str, voice: &str, text: &str) -> anyhow::Result<Vec<u8>>;
+}
+
+impl TtsEngine {
+    pub fn provider(self) -> &'static dyn TtsProvider {
+        match self {
+            Self::Lazypy => &Lazypy,
+            Self::Espeak => &Espeak,
+        }
+    }
+}
+
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::new)
+}
+
+/// The lazypy.ro proxy, which forwards to a bunch of online tts services.
+pub struct Lazypy;
+
+impl Lazypy {
+    /// Generates the speech and returns a link to it.
+    async fn speak_url(&self, service: &str, voice: &str, text: &str) -> anyhow::Result<String> {
+        tracing::info!("Fetching {}:{}:{:?}", service, voice, text);
+        let response = client()
+            .post("https://lazypy.ro/tts/proxy.php")
+            .form(&[("service", service), ("voice", voice), ("text", text)])
+            .send()
+            .await?
+            .json::<TtsResponse>()
+            .await?;
+        match response {
+            TtsResponse::Success { speak_url, .. } => {
+                tracing::info!("Playing {}", speak_url);
+                Ok(speak_url)
+            }
+            TtsResponse::Error { error } => Err(anyhow::anyhow!("failed to generate tts: {error}")),
+        }
+    }
+}
+
+#[serenity::async_trait]
+impl TtsProvider for Lazypy {
+    fn voices(&self) -> &'static [Voice] {
+        const VOICES: &[Voice] = &[
+            voice("Polly", "Brian", "English (UK)"),
+            voice("Polly", "Amy", "English (UK)"),
+            voice("Polly", "Emma", "English (UK)"),
+            voice("Polly", "Geraint", "English (Welsh)"),
+            voice("Polly", "Joanna", "English (US)"),
+            voice("Polly", "Matthew", "English (US)"),
+            voice("Polly", "Justin", "English (US)"),
+            voice("Polly", "Kimberly", "English (US)"),
+            voice("Polly", "Joey", "English (US)"),
+            voice("Polly", "Russell", "English (Australia)"),
+            voice("Polly", "Nicole", "English (Australia)"),
+            voice("Polly", "Raveena", "English (India)"),
+            voice("Polly", "Cristiano", "Portuguese (Portugal)"),
+            voice("Polly", "Ines", "Portuguese (Portugal)"),
+            voice("Polly", "Ricardo", "Portuguese (Brazil)"),
+            voice("Polly", "Vitoria", "Portuguese (Brazil)"),
+            voice("Polly", "Enrique", "Spanish (Spain)"),
+            voice("Polly", "Conchita", "Spanish (Spain)"),
+            voice("Polly", "Mathieu", "French"),
+            voice("Polly", "Celine", "French"),
+            voice("Polly", "Hans", "German"),
+            voice("Polly", "Marlene", "German"),
+            voice("Polly", "Giorgio", "Italian"),
+            voice("Polly", "Carla", "Italian"),
+            voice("Polly", "Takumi", "Japanese"),
+            voice("Polly", "Mizuki", "Japanese"),
+            voice("StreamElements", "Brian", "English (UK)"),
+            voice("StreamElements", "Justin", "English (US)"),
+            voice("TikTok", "en_us_001", "English (US)"),
+            voice("TikTok", "en_us_006", "English (US)"),
+            voice("TikTok", "en_uk_001", "English (UK)"),
+            voice("TikTok", "en_us_ghostface", "English (US)"),
+            voice("TikTok", "en_us_c3po", "English (US)"),
+            voice("TikTok", "br_001", "Portuguese (Brazil)"),
+        ];
+        VOICES
+    }
+
+    fn extension(&self) -> &'static str {
+        "mp3"
+    }
+
+    async fn generate(&self, service: &str, voice: &str, text: &str) -> anyhow::Result<Vec<u8>> {
+        let url = self.speak_url(service, voice, text).await?;
+        let audio = client()
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(audio.to_vec())
+    }
+}
+
+/*
+ * {success: true, speak_url: "ola"}
+ * {error: "reason"}
+ */
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum TtsResponse {
+    Success { success: bool, speak_url: String },
+    Error { error: String },
+}
+
+/// espeak-ng running locally, which works without a connection but sounds robotic.
+pub struct Espeak;
+
+#[serenity::async_trait]
+impl TtsProvider for Espeak {
+    fn voices(&self) -> &'static [Voice] {
+        const VOICES: &[Voice] = &[
+            voice("espeak-ng", "en", "English (UK)"),
+            voice("espeak-ng", "en-us", "English (US)"),
+            voice("espeak-ng", "pt", "Portuguese (Portugal)"),
+            voice("espeak-ng", "pt-br", "Portuguese (Brazil)"),
+            voice("espeak-ng", "es", "Spanish (Spain)"),
+            voice("espeak-ng", "fr", "French"),
+            voice("espeak-ng", "de", "German"),
+            voice("espeak-ng", "it", "Italian"),
+            voice("espeak-ng", "ja", "Japanese"),
+        ];
+        VOICES
+    }
+
+    fn extension(&self) -> &'static str {
+        "wav"
+    }
+
+    async fn generate(&self, _service: &str, voice: &str, text: &str) -> anyhow::Result<Vec<u8>> {
+        tracing::info!("Speaking {}:{:?}", voice, text);
+        let output = Command::new("espeak-ng")
+            .args(["--stdout", "-v", voice, "--", text])
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("failed to run espeak-ng")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "failed to generate tts: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_voices_ignoring_case() {
+        assert_eq!(Lazypy.find_voice(None, "brian").unwrap().service, "Polly");
+        assert_eq!(
+            Lazypy
+                .find_voice(Some("streamelements"), "Brian")
+                .unwrap()
+                .service,
+            "StreamElements"
+        );
+        assert!(Lazypy.find_voice(Some("TikTok"), "Brian").is_err());
+        assert!(Espeak.find_voice(None, "Brian").is_err());
+    }
+
+    #[test]
+    fn finds_autocompleted_voices_in_their_service() {
+        assert_eq!(
+            Lazypy
+                .find_voice(None, "StreamElements:Brian")
+                .unwrap()
+                .service,
+            "StreamElements"
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{commands::sfx, prefs};
+use anyhow::Context as _;
+use futures::FutureExt as _;
+use poise::command;
+use pubsub::events;
+use regex::{Captures, Regex};
+use reqwest::Url;
+use serenity::all::{ChannelId, Context, GuildId, Mention, Mentionable as _, Message};
+use std::{
+    collections::HashMap,
+    ops::ControlFlow,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+/// Longer messages are cut short, nobody wants to sit through an essay.
+const MAX_SPOKEN_CHARS: usize = 300;
+
+/// Keeps messages spoken in the order they were sent.
+static SPEAKING: LazyLock<Mutex<HashMap<GuildId, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Choose a channel whose messages are read out in voice, leave it empty to stop
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub(super) async fn reader(
+    ctx: super::super::Context<'_>,
+    channel: Option<ChannelId>,
+) -> anyhow::Result<()> {
+    prefs::guild::update(ctx.guild_id().context("Not in a guild")?, |p| {
+        p.tts_reader_channel = channel
+    })
+    .await?;
+    ctx.say(match channel {
+        Some(channel) => format!("Messages sent to {} will be read out", channel.mention()),
+        None => "Messages will no longer be read out".to_owned(),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Turns a message into something that can be read out.
+fn speakable(content: &str, name_of: impl Fn(Mention) -> Option<String>) -> String {
+    static MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<(@!?|@&|#)(\d+)>").unwrap());
+    static EMOJI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<a?:(\w+):\d+>").unwrap());
+    static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<?https?://[^\s>]+>?").unwrap());
+    static SPOILER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\|\|.*?\|\|").unwrap());
+
+    let text = SPOILER.replace_all(content, " spoiler ");
+    let text = URL.replace_all(&text, |c: &Captures| {
+        let url = c[0].trim_start_matches('<').trim_end_matches('>');
+        match Url::parse(url).ok().as_ref().and_then(|u| u.host_str()) {
+            Some(host) => format!(" a link to {} ", host.trim_start_matches("www.")),
+            None => " a link ".to_owned(),
+        }
+    });
+    let text = MENTION.replace_all(&text, |c: &Captures| {
+        let Ok(id) = c[2].parse::<u64>() else {
+            return String::new();
+        };
+        let mention = match &c[1] {
+            "@&" => Mention::Role(id.into()),
+            "#" => Mention::Channel(id.into()),
+            _ => Mention::User(id.into()),
+        };
+        format!(" {} ", name_of(mention).unwrap_or_default())
+    });
+    let text = EMOJI.replace_all(&text, |c: &Captures| format!(" {} ", &c[1]));
+    let text = text
+        .chars()
+        .filter(|c| !is_emoji(*c) && !matches!(c, '*' | '~' | '`' | '|' | '>'))
+        .map(|c| if c == '_' { ' ' } else { c })
+        .collect::<String>();
+    let mut spoken = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((end, _)) = spoken.char_indices().nth(MAX_SPOKEN_CHARS) {
+        spoken.truncate(end);
+    }
+    spoken
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D | 0xE0020..=0xE007F
+    )
+}
+
+/// Finds what a mention in the message refers to, preferring server nicknames.
+fn name_of(
+    ctx: &Context,
+    message: &Message,
+    guild_id: GuildId,
+    mention: Mention,
+) -> Option<String> {
+    let guild = ctx.cache.guild(guild_id);
+    match mention {
+        Mention::User(id) => guild
+            .as_ref()
+            .and_then(|g| g.members.get(&id))
+            .map(|m| m.display_name().to_owned())
+            .or_else(|| {
+                message
+                    .mentions
+                    .iter()
+                    .find(|u| u.id == id)
+                    .map(|u| u.display_name().to_owned())
+            }),
+        Mention::Role(id) => guild?.roles.get(&id).map(|r| r.name.clone()),
+        Mention::Channel(id) => guild?.channels.get(&id).map(|c| c.name.clone()),
+    }
+}
+
+async fn read(ctx: &Context, message: &Message) -> anyhow::Result<()> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot {
+        return Ok(());
+    }
+    let channel = prefs::guild::get(guild_id)
+        .await?
+        .and_then(|p| p.tts_reader_channel);
+    if channel != Some(message.channel_id) {
+        return Ok(());
+    }
+    let Some(voice_channel) = ctx
+        .cache
+        .guild(guild_id)
+        .and_then(|g| g.voice_states.get(&message.author.id)?.channel_id)
+    else {
+        return Ok(());
+    };
+    let text = speakable(&message.content, |m| name_of(ctx, message, guild_id, m));
+    if text.is_empty() {
+        return Ok(());
+    }
+    if sfx::util::busy_elsewhere(ctx, guild_id, voice_channel).await? {
+        return Ok(());
+    }
+    let bot = sfx::util::bot(ctx).await?;
+    let turn = SPEAKING
+        .lock()
+        .unwrap()
+        .entry(guild_id)
+        .or_default()
+        .clone();
+    let _turn = turn.lock().await;
+    let author = message.author.id;
+    sfx::enqueue_in_voice(ctx, &bot, guild_id, author, || async {
+        Ok(sfx::Sound {
+            name: format!("tts: {text}"),
+            input: super::speak(guild_id, author, &text).await?,
+            volume: 1.0,
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+pub(super) async fn initialize(events: &pubsub::EventBus) {
+    events
+        .subscribe::<events::Message, _>(|ctx, message: &Message| {
+            async move {
+                if let Err(e) = read(&ctx.serenity, message).await {
+                    tracing::error!("Failed to read out message: {e:#}");
+                }
+                ControlFlow::Continue(())
+            }
+            .boxed()
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(mention: Mention) -> Option<String> {
+        match mention {
+            Mention::User(id) if id.get() == 1 => Some("Memnarch".into()),
+            Mention::Role(id) if id.get() == 2 => Some("mods".into()),
+            Mention::Channel(id) if id.get() == 3 => Some("general".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_mentions() {
+        assert_eq!(
+            speakable("hi <@1>, <@!1> and <@&2>, go to <#3>", names),
+            "hi Memnarch , Memnarch and mods , go to general"
+        );
+        assert_eq!(speakable("who is <@4>?", names), "who is ?");
+    }
+
+    #[test]
+    fn names_custom_emoji_and_drops_the_rest() {
+        assert_eq!(
+            speakable("nice <:thumbs_up:123> <a:party:456> 🎉👍🏽", names),
+            "nice thumbs up party"
+        );
+    }
+
+    #[test]
+    fn replaces_links_and_spoilers() {
+        assert_eq!(
+            speakable(
+                "look https://www.youtube.com/watch?v=abc&t=1 ||he dies||",
+                names
+            ),
+            "look a link to youtube.com spoiler"
+        );
+        assert_eq!(speakable("**bold** `code`", names), "bold code");
+    }
+
+    #[test]
+    fn cuts_long_messages() {
+        assert_eq!(speakable(&"a".repeat(1000), names).len(), MAX_SPOKEN_CHARS);
+        assert_eq!(speakable("🎉", names), "");
+    }
+}
@@ -0,0 +1,188 @@
+use super::provider::Voice;
+use crate::{
+    commands::sfx::{self, util},
+    prefs::guild::TtsEngine,
+};
+use anyhow::Context as _;
+use futures::FutureExt as _;
+use itertools::Itertools;
+use poise::{ChoiceParameter as _, CreateReply, command};
+use pubsub::events;
+use serenity::all::{
+    AutocompleteChoice, ButtonStyle, ComponentInteraction, Context, CreateActionRow, CreateButton,
+    GuildId, Interaction,
+};
+use std::ops::ControlFlow;
+
+const PREVIEW_BUTTON: &str = "tts-preview:";
+const PAGE_BUTTON: &str = "tts-voices:";
+
+/// List the voices tts can speak with in this server
+#[command(slash_command, guild_only)]
+pub(super) async fn voices(
+    ctx: super::super::Context<'_>,
+    #[description = "Only list the voices of this service"]
+    #[autocomplete = "autocomplete_service"]
+    service: Option<String>,
+) -> anyhow::Result<()> {
+    let engine = super::engine_of(ctx.guild_id()).await?;
+    let (content, components) = page(engine, 0, service.as_deref())?;
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components),
+    )
+    .await?;
+    Ok(())
+}
+
+/// A button that speaks a sample sentence with the voice in the presser's voice channel.
+pub(super) fn preview_button(voice: &Voice) -> CreateButton {
+    CreateButton::new(format!("{PREVIEW_BUTTON}{}:{}", voice.service, voice.name))
+        .label(format!("{} · {}", voice.name, voice.service))
+        .style(ButtonStyle::Secondary)
+}
+
+fn page(
+    engine: TtsEngine,
+    page: usize,
+    service: Option<&str>,
+) -> anyhow::Result<(String, Vec<CreateActionRow>)> {
+    let voices = engine
+        .provider()
+        .voices()
+        .iter()
+        .filter(|v| service.is_none_or(|s| v.service.eq_ignore_ascii_case(s)))
+        .collect::<Vec<_>>();
+    if voices.is_empty() {
+        anyhow::bail!(
+            "{} has no service called {}",
+            engine.name(),
+            service.unwrap_or_default()
+        );
+    }
+    let (page, pages, voices) = util::button_page(&voices, page);
+    let controls = util::page_buttons(page, pages, |page, direction| {
+        Ok(format!(
+            "{PAGE_BUTTON}{page}:{direction}:{}",
+            service.unwrap_or_default()
+        ))
+    })?;
+    let rows = util::button_rows(voices.iter().map(|v| preview_button(v)).collect(), controls);
+    let content = format!(
+        "**Voices of {}** ({}/{pages}), press one to hear it\n{}",
+        engine.name(),
+        page + 1,
+        voices.iter().format_with("\n", |v, f| f(&format_args!(
+            "**{}** from {}, {}",
+            v.name, v.service, v.language
+        )))
+    );
+    Ok((content, rows))
+}
+
+pub(super) async fn autocomplete_service(
+    ctx: super::super::Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let engine = super::engine_of(ctx.guild_id()).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to autocomplete tts service: {}", e);
+        TtsEngine::default()
+    });
+    let partial = partial.to_lowercase();
+    engine
+        .provider()
+        .voices()
+        .iter()
+        .map(|v| v.service)
+        .unique()
+        .filter(move |s| s.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|s| AutocompleteChoice::new(s, s))
+}
+
+pub(super) async fn autocomplete_voice(
+    ctx: super::super::Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = AutocompleteChoice> {
+    let engine = super::engine_of(ctx.guild_id()).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to autocomplete tts voice: {}", e);
+        TtsEngine::default()
+    });
+    let partial = partial.to_lowercase();
+    engine
+        .provider()
+        .voices()
+        .iter()
+        .filter(move |v| {
+            v.name.to_lowercase().contains(&partial) || v.language.to_lowercase().contains(&partial)
+        })
+        .take(25)
+        .map(|v| {
+            AutocompleteChoice::new(
+                format!("{} ({}, {})", v.name, v.service, v.language),
+                format!("{}:{}", v.service, v.name),
+            )
+        })
+}
+
+async fn preview(ctx: &Context, press: &ComponentInteraction, guild_id: GuildId, id: &str) {
+    util::acknowledge_press(ctx, press, async {
+        let (service, name) = id.split_once(':').context("Invalid button")?;
+        let provider = super::engine_of(Some(guild_id)).await?.provider();
+        let voice = provider.find_voice(Some(service), name)?;
+        let bot = util::bot(ctx).await?;
+        sfx::play_in_voice(ctx, &bot, guild_id, press.user.id, || async {
+            Ok(sfx::Sound {
+                name: format!("tts preview: {}", voice.name),
+                input: super::speak_with(
+                    provider,
+                    voice,
+                    &format!("Hi, I'm {}. This is how I sound.", voice.name),
+                )
+                .await?,
+                volume: 1.0,
+            })
+        })
+        .await?;
+        Ok(())
+    })
+    .await;
+}
+
+async fn turn_page(ctx: &Context, press: &ComponentInteraction, guild_id: GuildId, id: &str) {
+    let mut parts = id.splitn(3, ':');
+    let page_number = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    let service = parts.nth(1).filter(|s| !s.is_empty());
+    let page = match super::engine_of(Some(guild_id)).await {
+        Ok(engine) => page(engine, page_number, service),
+        Err(e) => Err(e.into()),
+    };
+    util::turn_page(ctx, press, page).await;
+}
+
+async fn handle_press(ctx: &Context, i: &Interaction) {
+    let Some(press) = i.as_message_component() else {
+        return;
+    };
+    let Some(guild_id) = press.guild_id else {
+        return;
+    };
+    if let Some(id) = press.data.custom_id.strip_prefix(PREVIEW_BUTTON) {
+        preview(ctx, press, guild_id, id).await;
+    } else if let Some(id) = press.data.custom_id.strip_prefix(PAGE_BUTTON) {
+        turn_page(ctx, press, guild_id, id).await;
+    }
+}
+
+pub(super) async fn initialize(events: &pubsub::EventBus) {
+    events
+        .subscribe::<events::InteractionCreate, _>(|ctx, i| {
+            async move {
+                handle_press(&ctx.serenity, i).await;
+                ControlFlow::Continue(())
+            }
+            .boxed()
+        })
+        .await;
+}
@@ -0,0 +1,341 @@
+use super::sfx::{self, StatsPeriod};
+use crate::{
+    features::{disconnect_channel, voice_activity},
+    prefs::{self, guild::DisconnectChannel},
+};
+use anyhow::Context as _;
+use chrono::{Duration, Utc};
+use itertools::Itertools;
+use poise::{ChoiceParameter as _, CreateReply, command};
+use serenity::all::{ChannelId, CreateEmbed, Mentionable as _, RoleId, User};
+use std::fmt::Write as _;
+
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("join", "leave", "status", "timeout", "stats", "disconnect")
+)]
+pub async fn voice(_: super::Context<'_>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Bring the bot to your voice channel
+#[command(slash_command, guild_only)]
+async fn join(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let channel = ctx
+        .guild()
+        .and_then(|g| g.voice_states.get(&ctx.author().id)?.channel_id)
+        .context("You're not in a voice channel")?;
+    songbird::get(ctx.serenity_context())
+        .await
+        .context("Songbird not initialized")?
+        .join(guild_id, channel)
+        .await?;
+    sfx::schedule_leave(ctx.serenity_context(), ctx.data(), guild_id).await?;
+    ctx.say(format!("Joined {}", channel.mention())).await?;
+    Ok(())
+}
+
+/// Make the bot leave the voice channel
+#[command(slash_command, guild_only)]
+async fn leave(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let songbird = songbird::get(ctx.serenity_context())
+        .await
+        .context("Songbird not initialized")?;
+    if songbird.get(guild_id).is_none() {
+        ctx.say("I'm not in a voice channel").await?;
+        return Ok(());
+    }
+    songbird.remove(guild_id).await?;
+    let bot = ctx.data();
+    bot.leave_voice
+        .lock()
+        .await
+        .remove(&mut *bot.daemons.lock().await, guild_id)
+        .await;
+    ctx.say("Bye!").await?;
+    Ok(())
+}
+
+/// Show where the bot is, what it's going to play and when it's leaving
+#[command(slash_command, guild_only)]
+async fn status(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let call = songbird::get(ctx.serenity_context())
+        .await
+        .context("Songbird not initialized")?
+        .get(guild_id);
+    let Some(call) = call else {
+        ctx.say("I'm not in a voice channel").await?;
+        return Ok(());
+    };
+    let (channel, queue) = {
+        let call = call.lock().await;
+        (
+            call.current_channel()
+                .map(|c| ChannelId::new(c.0.get()).mention().to_string()),
+            call.queue().current_queue(),
+        )
+    };
+    let leaving_at = ctx.data().leave_voice.lock().await.leaving_at(guild_id);
+    let mut status = format!(
+        "**Channel:** {}\n",
+        channel.as_deref().unwrap_or("joining...")
+    );
+    match leaving_at {
+        Some(when) if when > Utc::now() => {
+            writeln!(status, "**Leaving:** <t:{}:R>", when.timestamp())?
+        }
+        _ => writeln!(status, "**Leaving:** once everyone else leaves")?,
+    }
+    let mut tracks = queue.iter().map(|t| t.data::<String>());
+    match tracks.next() {
+        Some(playing) => write!(
+            status,
+            "**Playing:** {playing}\n{}",
+            tracks
+                .enumerate()
+                .format_with("\n", |(i, t), f| f(&format_args!("{}. {t}", i + 1)))
+        )?,
+        None => write!(status, "Nothing queued")?,
+    }
+    ctx.say(status).await?;
+    Ok(())
+}
+
+/// Choose how long the bot stays in a silent voice channel
+#[command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn timeout(
+    ctx: super::Context<'_>,
+    #[description = "Minutes to wait before leaving, 0 to stay until everyone leaves"]
+    #[max = 1440]
+    minutes: u64,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    prefs::guild::update(guild_id, |p| p.voice_idle_timeout = Some(minutes)).await?;
+    let in_call = songbird::get(ctx.serenity_context())
+        .await
+        .context("Songbird not initialized")?
+        .get(guild_id)
+        .is_some();
+    if in_call {
+        sfx::schedule_leave(ctx.serenity_context(), ctx.data(), guild_id).await?;
+    }
+    ctx.say(match minutes {
+        0 => "I'll stay in voice until everyone else leaves".to_owned(),
+        1 => "I'll leave voice after a minute of silence".to_owned(),
+        m => format!("I'll leave voice after {m} minutes of silence"),
+    })
+    .await?;
+    Ok(())
+}
+
+fn format_duration(d: Duration) -> String {
+    match (d.num_hours(), d.num_minutes() % 60) {
+        (0, 0) => "<1m".to_owned(),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m:02}m"),
+    }
+}
+
+/// Show who spends the most time in voice
+#[command(slash_command, guild_only)]
+async fn stats(
+    ctx: super::Context<'_>,
+    #[description = "Which sessions to count, defaults to a week"] period: Option<StatsPeriod>,
+    #[description = "Whose totals to show, defaults to yours"] member: Option<User>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let period = period.unwrap_or(StatsPeriod::Week);
+    let member = member.as_ref().unwrap_or(ctx.author());
+    let sessions = voice_activity::sessions(guild_id, period.since()).await?;
+    if sessions.is_empty() {
+        ctx.say("Nobody has been in voice").await?;
+        return Ok(());
+    }
+    let totals = voice_activity::totals(&sessions);
+    let top = totals
+        .iter()
+        .take(10)
+        .enumerate()
+        .format_with("\n", |(rank, (user, total)), f| {
+            f(&format_args!(
+                "{}. **{}** {}",
+                rank + 1,
+                format_duration(*total),
+                user.mention()
+            ))
+        })
+        .to_string();
+    let longest = sessions
+        .iter()
+        .sorted_unstable_by_key(|s| std::cmp::Reverse(s.duration()))
+        .take(5)
+        .format_with("\n", |s, f| {
+            f(&format_args!(
+                "**{}** {} in {}, <t:{}:d>",
+                format_duration(s.duration()),
+                s.user.mention(),
+                s.channel.mention(),
+                s.start.timestamp()
+            ))
+        })
+        .to_string();
+    let own = sessions
+        .iter()
+        .filter(|s| s.user == member.id)
+        .collect::<Vec<_>>();
+    let own = match own.iter().map(|s| s.duration()).max() {
+        Some(longest) => format!(
+            "**{}** over {} sessions, the longest was **{}**",
+            format_duration(own.iter().map(|s| s.duration()).sum()),
+            own.len(),
+            format_duration(longest)
+        ),
+        None => "Hasn't been in voice".to_owned(),
+    };
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(format!("Voice activity ({})", period.name()))
+                .field("Top talkers", top, false)
+                .field("Longest sessions", longest, false)
+                .field(member.display_name(), own, false),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Manage the voice channel that disconnects everyone who joins it
+#[command(
+    slash_command,
+    guild_only,
+    subcommands("disconnect_set", "disconnect_exempt", "disconnect_stats")
+)]
+async fn disconnect(_: super::Context<'_>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Choose the channel that disconnects everyone who joins it, leave it empty to have none
+#[command(
+    slash_command,
+    guild_only,
+    rename = "set",
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn disconnect_set(
+    ctx: super::Context<'_>,
+    channel: Option<ChannelId>,
+    #[description = "An sfx to play before disconnecting everyone"]
+    #[autocomplete = "sfx::autocomplete_sfx"]
+    sfx: Option<String>,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let clip = match sfx {
+        Some(query) => Some(sfx::find_file(guild_id, &query).await?),
+        None => None,
+    };
+    prefs::guild::update(guild_id, |p| {
+        p.disconnect_channel = channel.map(|channel| DisconnectChannel {
+            channel,
+            exempt_roles: p
+                .disconnect_channel
+                .take()
+                .map(|c| c.exempt_roles)
+                .unwrap_or_default(),
+            sfx: clip.as_ref().map(|f| f.to_string_lossy().into_owned()),
+        })
+    })
+    .await?;
+    ctx.say(match (channel, &clip) {
+        (Some(channel), Some(clip)) => format!(
+            "Joining {} will play **{}** and disconnect you",
+            channel.mention(),
+            clip.file_name().unwrap_or_default().display()
+        ),
+        (Some(channel), None) => format!("Joining {} will disconnect you", channel.mention()),
+        (None, _) => "There's no disconnect channel anymore".to_owned(),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Let members with a role stay in the disconnect channel
+#[command(
+    slash_command,
+    guild_only,
+    rename = "exempt",
+    default_member_permissions = "ADMINISTRATOR"
+)]
+async fn disconnect_exempt(
+    ctx: super::Context<'_>,
+    role: RoleId,
+    exempt: bool,
+) -> anyhow::Result<()> {
+    let guild_id = ctx.guild_id().context("Not in a guild")?;
+    let updated = prefs::guild::update(guild_id, |p| {
+        let Some(config) = &mut p.disconnect_channel else {
+            return false;
+        };
+        if exempt {
+            config.exempt_roles.insert(role);
+        } else {
+            config.exempt_roles.remove(&role);
+        }
+        true
+    })
+    .await?;
+    anyhow::ensure!(updated, "There's no disconnect channel, set one first");
+    ctx.say(format!(
+        "{} {} be disconnected",
+        role.mention(),
+        if exempt { "will no longer" } else { "will" }
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Show who fell for the disconnect channel the most
+#[command(slash_command, guild_only, rename = "stats")]
+async fn disconnect_stats(ctx: super::Context<'_>) -> anyhow::Result<()> {
+    let caught = disconnect_channel::caught(ctx.guild_id().context("Not in a guild")?).await?;
+    if caught.is_empty() {
+        ctx.say("Nobody has fallen for it yet").await?;
+        return Ok(());
+    }
+    ctx.say(format!(
+        "**Fell for it {} times**\n{}",
+        caught.values().sum::<u64>(),
+        caught
+            .iter()
+            .sorted_unstable_by(|(a_id, a), (b_id, b)| b.cmp(a).then_with(|| a_id.cmp(b_id)))
+            .take(10)
+            .enumerate()
+            .format_with("\n", |(rank, (user, count)), f| f(&format_args!(
+                "{}. **{count}** {}",
+                rank + 1,
+                user.mention()
+            )))
+    ))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::seconds(30)), "<1m");
+        assert_eq!(format_duration(Duration::minutes(42)), "42m");
+        assert_eq!(format_duration(Duration::minutes(125)), "2h 05m");
+    }
+}
@@ -1,40 +1,224 @@
+use crate::{
+    commands::sfx,
+    in_files,
+    prefs::{self, guild::DisconnectChannel},
+};
+use anyhow::Context as _;
 use futures::{FutureExt as _, StreamExt as _, stream};
+use json_db::GlobalDatabase;
 use pubsub::events;
-use serenity::all::{ChannelId, Context, GuildId};
-use std::ops::ControlFlow;
+use serenity::all::{ChannelId, Context, GuildId, Member, UserId, VoiceState};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    ops::ControlFlow,
+    path::Path,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Exists once the hardcoded channel has been moved to the guild's prefs.
+const MIGRATED: &str = in_files!("disconnect_channel.migrated");
+
+/// How many times each member of each guild has been disconnected.
+static CAUGHT: GlobalDatabase<HashMap<GuildId, HashMap<UserId, u64>>> =
+    GlobalDatabase::new(in_files!("disconnect_channel_caught.json"));
+
+/// Guilds whose disconnect channel is being emptied.
+static BUSY: LazyLock<Mutex<HashSet<GuildId>>> = LazyLock::new(Default::default);
+
+/// Longest the sfx is given to play before everyone is disconnected anyway.
+const MAX_SFX_WAIT: Duration = Duration::from_secs(30);
+
+/// How many times the members of a guild have been disconnected.
+pub async fn caught(guild_id: GuildId) -> anyhow::Result<HashMap<UserId, u64>> {
+    Ok(CAUGHT
+        .load()
+        .await?
+        .get(&guild_id)
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Keeps playing an sfx that was renamed, or stops playing it once it's deleted.
+pub async fn sfx_moved(from: &Path, to: Option<&Path>) -> io::Result<()> {
+    let from = from.to_string_lossy();
+    for (guild_id, guild_prefs) in prefs::guild::all().await? {
+        let sfx = guild_prefs.disconnect_channel.and_then(|d| d.sfx);
+        if sfx.as_deref() != Some(&*from) {
+            continue;
+        }
+        prefs::guild::update(guild_id, |p| {
+            if let Some(config) = &mut p.disconnect_channel {
+                config.sfx = to.map(|to| to.to_string_lossy().into_owned());
+            }
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// The channel used to be hardcoded for mirrodin, this keeps it working there.
+async fn migrate() -> anyhow::Result<()> {
+    const MIRRODIN: GuildId = GuildId::new(352399774818762759);
+    if Path::new(MIGRATED).exists() {
+        return Ok(());
+    }
+    prefs::guild::update(MIRRODIN, |p| {
+        p.disconnect_channel
+            .get_or_insert_with(|| DisconnectChannel {
+                channel: ChannelId::new(707561909846802462),
+                exempt_roles: Default::default(),
+                sfx: None,
+            });
+    })
+    .await?;
+    fs::write(MIGRATED, "")?;
+    Ok(())
+}
+
+fn is_exempt(config: &DisconnectChannel, member: &Member) -> bool {
+    member.user.bot || member.roles.iter().any(|r| config.exempt_roles.contains(r))
+}
+
+/// Plays the sfx and waits for it to end, returns whether the bot joined to play it.
+async fn play_sfx(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel: ChannelId,
+    user: UserId,
+    file: &Path,
+) -> anyhow::Result<bool> {
+    if !file.exists() {
+        return Ok(false);
+    }
+    if sfx::util::busy_elsewhere(ctx, guild_id, channel).await? {
+        return Ok(false);
+    }
+    let in_call = songbird::get(ctx)
+        .await
+        .context("Songbird not initialized")?
+        .get(guild_id)
+        .is_some();
+    let bot = sfx::util::bot(ctx).await?;
+    let handle = sfx::play_file(ctx, &bot, guild_id, user, file).await?;
+    let started = Instant::now();
+    while started.elapsed() < MAX_SFX_WAIT
+        && handle
+            .get_info()
+            .await
+            .is_ok_and(|info| !info.playing.is_done())
+    {
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    Ok(!in_call)
+}
+
+async fn handle(ctx: &Context, old: Option<&VoiceState>, new: &VoiceState) -> anyhow::Result<()> {
+    let (Some(guild_id), Some(channel)) = (new.guild_id, new.channel_id) else {
+        return Ok(());
+    };
+    if old.and_then(|vs| vs.channel_id) == Some(channel) {
+        return Ok(());
+    }
+    let Some(config) = prefs::guild::get(guild_id)
+        .await?
+        .and_then(|p| p.disconnect_channel)
+        .filter(|c| c.channel == channel)
+    else {
+        return Ok(());
+    };
+    if new.member.as_ref().is_some_and(|m| is_exempt(&config, m)) {
+        return Ok(());
+    }
+    // already disconnecting everyone in there, whoever just joined included
+    if !BUSY.lock().unwrap().insert(guild_id) {
+        return Ok(());
+    }
+    // the sfx takes a while, other subscribers shouldn't wait for it
+    let (ctx, user) = (ctx.clone(), new.user_id);
+    tokio::spawn(async move {
+        if let Err(e) = disconnect_everyone(&ctx, guild_id, channel, user, config).await {
+            tracing::error!("Failed to disconnect members: {e:#}");
+        }
+        BUSY.lock().unwrap().remove(&guild_id);
+    });
+    Ok(())
+}
+
+async fn disconnect_everyone(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel: ChannelId,
+    user: UserId,
+    config: DisconnectChannel,
+) -> anyhow::Result<()> {
+    let joined = match &config.sfx {
+        Some(file) => play_sfx(ctx, guild_id, channel, user, Path::new(file))
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to play the disconnect channel sfx: {e:#}");
+                false
+            }),
+        None => false,
+    };
+    let members = channel
+        .to_channel(ctx)
+        .await?
+        .guild()
+        .context("Not a guild channel")?
+        .members(ctx)?
+        .into_iter()
+        .filter(|m| !is_exempt(&config, m));
+    let disconnected = stream::iter(members)
+        .filter_map(|m| async move {
+            let id = m.user.id;
+            match guild_id.disconnect_member(ctx, m).await {
+                Ok(_) => Some(id),
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to disconnect member {} from disconnect channel: {}",
+                        id,
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+        .await;
+    {
+        let mut caught = CAUGHT.load().await?;
+        let caught = caught.entry(guild_id).or_default();
+        for id in disconnected {
+            *caught.entry(id).or_default() += 1;
+        }
+    }
+    if joined {
+        songbird::get(ctx)
+            .await
+            .context("Songbird not initialized")?
+            .remove(guild_id)
+            .await?;
+        let bot = sfx::util::bot(ctx).await?;
+        bot.leave_voice
+            .lock()
+            .await
+            .remove(&mut *bot.daemons.lock().await, guild_id)
+            .await;
+    }
+    Ok(())
+}
 
 pub async fn initialize(events: &pubsub::EventBus) {
+    if let Err(e) = migrate().await {
+        tracing::error!("Failed to migrate the disconnect channel: {e:#}");
+    }
     events
-        .subscribe::<events::VoiceStateUpdate, _>(|ctx, events::VoiceStateUpdate { new, .. }| {
+        .subscribe::<events::VoiceStateUpdate, _>(|ctx, events::VoiceStateUpdate { old, new }| {
             async move {
-                // Disconnect channel of mirrodin
-                if let (Some(gid @ 352399774818762759), Some(id @ 707561909846802462)) = (
-                    new.guild_id.map(|i| i.get()),
-                    new.channel_id.map(|i| i.get()),
-                ) {
-                    async fn f(id: ChannelId, gid: GuildId, ctx: &Context) -> anyhow::Result<()> {
-                        #[allow(clippy::result_large_err)]
-                        let c = id.to_channel(ctx).await.and_then(|c| {
-                            c.guild()
-                                .ok_or(serenity::Error::Other("Not a guild channel"))
-                        })?;
-                        stream::iter(c.members(ctx)?)
-                            .for_each(|mut m| async move {
-                                let name = std::mem::take(&mut m.user.name);
-                                if let Err(e) = gid.disconnect_member(ctx, m).await {
-                                    tracing::error!(
-                                    "Failed to disconnect member {} from disconnect channel: {}",
-                                    name,
-                                    e
-                                );
-                                }
-                            })
-                            .await;
-                        Ok(())
-                    }
-                    if let Err(e) = f(id.into(), gid.into(), &ctx.serenity).await {
-                        tracing::error!("Failed to disconnect user: {}", e);
-                    }
+                if let Err(e) = handle(&ctx.serenity, old.as_ref(), new).await {
+                    tracing::error!("Failed to disconnect user: {e:#}");
                 }
                 ControlFlow::Continue(())
             }
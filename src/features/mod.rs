@@ -8,3 +8,4 @@ pub mod music_channel_broadcast;
 pub mod owner;
 pub mod quotes;
 pub mod reminders;
+pub mod voice_activity;
@@ -1 +1,2 @@
+pub mod mod_log;
 pub mod reaction_roles;
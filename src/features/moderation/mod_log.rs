@@ -0,0 +1,32 @@
+use crate::prefs;
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage, GuildId, Http, Mentionable as _};
+use std::io;
+
+/// Sets the guild's mod log channel, or stops logging, and says what changed.
+pub async fn set_channel(guild_id: GuildId, channel: Option<ChannelId>) -> io::Result<String> {
+    prefs::guild::update(guild_id, |p| p.mod_log_channel = channel).await?;
+    Ok(match channel {
+        Some(channel) => format!("Logging to {}", channel.mention()),
+        None => "Logging disabled".to_owned(),
+    })
+}
+
+/// Posts in the guild's mod log channel, if it has one.
+pub async fn log(http: &Http, guild_id: GuildId, embed: CreateEmbed) {
+    let channel = match prefs::guild::get(guild_id).await {
+        Ok(prefs) => prefs.and_then(|p| p.mod_log_channel),
+        Err(e) => {
+            tracing::error!("failed to load guild prefs of {guild_id}: {e:?}");
+            return;
+        }
+    };
+    let Some(channel) = channel else {
+        return;
+    };
+    if let Err(e) = channel
+        .send_message(http, CreateMessage::new().embed(embed))
+        .await
+    {
+        tracing::error!("failed to post in the mod log of {guild_id}: {e:?}");
+    }
+}
@@ -1,18 +1,90 @@
-use crate::in_files;
-use rand::seq::IndexedRandom;
+use crate::{
+    in_files,
+    prefs::guild::{self as guild_prefs, QuoteOfTheDay},
+    util::daemons::DaemonManager,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use daemons::{ControlFlow, Daemon, async_trait};
+use futures::FutureExt as _;
+use json_db::GlobalDatabase;
+use mappable_rc::Marc;
+use pubsub::events;
+use rand::{
+    distr::{Distribution as _, weighted::WeightedIndex},
+    seq::SliceRandom,
+};
 use serde::{Deserialize, Serialize};
-use std::{io, path::PathBuf};
+use serenity::all::{
+    ButtonStyle, Context, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, GuildId, Http,
+    Interaction, UserId,
+};
+use simsearch::SimSearch;
+use std::{
+    collections::{HashMap, HashSet},
+    io, iter,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration as StdDuration,
+};
 use tokio::{
-    fs::{DirBuilder, File},
-    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    fs::{self, DirBuilder, File},
+    io::AsyncReadExt as _,
+    sync::Mutex,
 };
 
 const QUOTES_DIR: &str = "quotes";
 const QUOTES_FILE: &str = "quotes.json";
+/// How many previous versions of the quotes file are kept around.
+const BACKUPS: usize = 5;
+const BACKUP_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60 * 24);
+const UPVOTE_BUTTON: &str = "quote-upvote:";
+/// Failed posts in a row after which the quote of the day is disabled.
+const MAX_FAILURES: u32 = 10;
+
+static ROTATIONS: GlobalDatabase<HashMap<GuildId, Rotation>> =
+    GlobalDatabase::new(in_files!(QUOTES_DIR, "rotations.json"));
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "QuoteRepr")]
+pub struct Quote {
+    pub text: String,
+    pub voters: HashSet<UserId>,
+}
+
+impl Quote {
+    pub fn score(&self) -> usize {
+        self.voters.len()
+    }
+}
+
+/// Quotes used to be stored as plain strings, before they could be voted on.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuoteRepr {
+    Text(String),
+    Quote {
+        text: String,
+        #[serde(default)]
+        voters: HashSet<UserId>,
+    },
+}
+
+impl From<QuoteRepr> for Quote {
+    fn from(q: QuoteRepr) -> Self {
+        match q {
+            QuoteRepr::Text(text) => Self {
+                text,
+                voters: Default::default(),
+            },
+            QuoteRepr::Quote { text, voters } => Self { text, voters },
+        }
+    }
+}
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct QuoteManager(Vec<String>);
+pub struct QuoteManager(Vec<Quote>);
 
 impl QuoteManager {
     async fn path() -> std::io::Result<PathBuf> {
@@ -24,34 +96,463 @@ impl QuoteManager {
         Ok(p)
     }
 
-    pub(crate) async fn load() -> std::io::Result<Self> {
-        let path = Self::path().await?;
+    fn backup_path(path: &Path, n: usize) -> PathBuf {
+        let mut name = path
+            .file_name()
+            .expect("This path always has a file name")
+            .to_owned();
+        name.push(format!(".{n}"));
+        path.with_file_name(name)
+    }
+
+    async fn read(path: &Path) -> io::Result<Option<Self>> {
         let mut file = match File::open(path).await {
             Ok(f) => f,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
             Err(e) => return Err(e),
         };
         let mut s = String::new();
-        file.read_to_string(&mut s).await.and_then(|_| {
-            serde_json::from_str(&s).map_err(|e| {
-                tracing::error!("Error parsing quotes");
-                e.into()
-            })
-        })
+        file.read_to_string(&mut s).await?;
+        Ok(Some(serde_json::from_str(&s)?))
+    }
+
+    pub(crate) async fn load() -> std::io::Result<Self> {
+        Self::load_from(&Self::path().await?).await
     }
 
-    pub fn choose(&self) -> Option<&str> {
-        self.0.choose(&mut rand::rng()).map(|x| x.as_str())
+    /// Loads the quotes, falling back to the newest readable backup, or else to no quotes.
+    async fn load_from(path: &Path) -> io::Result<Self> {
+        let mut error = None;
+        for p in
+            iter::once(path.to_owned()).chain((1..=BACKUPS).map(|n| Self::backup_path(path, n)))
+        {
+            match Self::read(&p).await {
+                Ok(Some(quotes)) => {
+                    if error.is_some() {
+                        tracing::warn!("Recovered quotes from backup {:?}", p);
+                    }
+                    return Ok(quotes);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!("Error parsing quotes from {:?}: {}", p, e);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(e) = error {
+            tracing::error!("No readable quotes, starting without any: {}", e);
+            let mut name = path
+                .file_name()
+                .expect("This path always has a file name")
+                .to_owned();
+            name.push(".corrupt");
+            match fs::rename(path, path.with_file_name(name)).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    tracing::error!("Could not move the corrupt quotes aside: {}", e)
+                }
+                _ => {}
+            }
+        }
+        Ok(Default::default())
+    }
+
+    /// Backs up the current quotes file, at most once per [`BACKUP_INTERVAL`].
+    async fn rotate_backups(path: &Path) -> io::Result<()> {
+        let last_backup = fs::metadata(Self::backup_path(path, 1))
+            .await
+            .and_then(|m| m.modified());
+        if last_backup.is_ok_and(|t| t.elapsed().is_ok_and(|age| age < BACKUP_INTERVAL)) {
+            return Ok(());
+        }
+        if !matches!(Self::read(path).await, Ok(Some(_))) {
+            return Ok(());
+        }
+        for n in (1..BACKUPS).rev() {
+            match fs::rename(Self::backup_path(path, n), Self::backup_path(path, n + 1)).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::copy(path, Self::backup_path(path, 1)).await?;
+        Ok(())
+    }
+
+    async fn save(&self) -> io::Result<()> {
+        self.save_to(&Self::path().await?).await
+    }
+
+    async fn save_to(&self, path: &Path) -> io::Result<()> {
+        let (file, tmp_path) =
+            tempfile::NamedTempFile::new_in(path.parent().unwrap())?.into_parts();
+        serde_json::to_writer(&file, self)?;
+        file.sync_all()?;
+        Self::rotate_backups(path).await?;
+        tmp_path.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Picks a random quote, giving better voted quotes a higher chance of being picked.
+    pub fn choose(&self) -> Option<(usize, &Quote)> {
+        let weights = WeightedIndex::new(self.0.iter().map(|q| 1 + q.score())).ok()?;
+        let id = weights.sample(&mut rand::rng());
+        Some((id, &self.0[id]))
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Quote> {
+        self.0.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.0.iter().map(|x| x.text.as_str()).enumerate()
+    }
+
+    /// The quotes sorted from most to least voted.
+    pub fn top(&self) -> Vec<(usize, &Quote)> {
+        let mut quotes = self.0.iter().enumerate().collect::<Vec<_>>();
+        quotes.sort_by_key(|(_, q)| std::cmp::Reverse(q.score()));
+        quotes
+    }
+
+    /// Toggles `user`'s vote on a quote, returning the quote's new score.
+    pub async fn vote(&mut self, id: usize, user: UserId) -> io::Result<Option<usize>> {
+        let Some(quote) = self.0.get_mut(id) else {
+            return Ok(None);
+        };
+        if !quote.voters.remove(&user) {
+            quote.voters.insert(user);
+        }
+        let score = quote.score();
+        self.save().await?;
+        Ok(Some(score))
+    }
+
+    /// Fuzzy search the quotes, returning their ids and text ordered from best to worst match.
+    pub fn search(&self, query: &str) -> Vec<(usize, &str)> {
+        let search = self
+            .iter()
+            .fold(SimSearch::new(), |mut search, (id, quote)| {
+                search.insert(id, quote);
+                search
+            });
+        search
+            .search(query)
+            .into_iter()
+            .map(|id| (id, self.0[id].text.as_str()))
+            .collect()
     }
 
     pub async fn add(&mut self, quote: String) -> std::io::Result<()> {
-        self.0.push(quote);
-        let path = Self::path().await?;
-        tracing::trace!("Quote add: {:?}", path);
-        let content = serde_json::to_string(self)?;
-        File::create(path)
-            .await?
-            .write_all(content.as_bytes())
+        self.0.push(Quote {
+            text: quote,
+            voters: Default::default(),
+        });
+        tracing::trace!("Quote add: {:?}", self.0.last());
+        self.save().await
+    }
+}
+
+/// The quotes a guild hasn't seen yet in the current cycle of the quote of the day.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Rotation {
+    #[serde(default)]
+    remaining: Vec<usize>,
+    #[serde(default)]
+    last_posted: Option<NaiveDate>,
+    #[serde(default)]
+    failures: u32,
+    #[serde(default)]
+    retry_at: Option<DateTime<Utc>>,
+}
+
+impl Rotation {
+    /// The quote to post next, it stays in the rotation until it's been posted.
+    fn upcoming(&mut self, len: usize) -> Option<usize> {
+        self.remaining.retain(|&id| id < len);
+        if self.remaining.is_empty() {
+            self.remaining = (0..len).collect();
+            self.remaining.shuffle(&mut rand::rng());
+        }
+        self.remaining.last().copied()
+    }
+
+    fn posted(&mut self, date: NaiveDate) {
+        self.remaining.pop();
+        self.last_posted = Some(date);
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    /// Backs off after a failed post, returns whether to give up on posting entirely.
+    fn failed(&mut self, now: DateTime<Utc>) -> bool {
+        self.failures += 1;
+        self.retry_at = Some(now + Duration::minutes(1 << self.failures.min(MAX_FAILURES)));
+        self.failures >= MAX_FAILURES
+    }
+}
+
+/// The upvote button to attach to a displayed quote.
+pub fn vote_button(id: usize, quote: &Quote) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{UPVOTE_BUTTON}{id}"))
+            .style(ButtonStyle::Secondary)
+            .emoji('👍')
+            .label(quote.score().to_string()),
+    ])
+}
+
+async fn handle_vote(ctx: &Context, quotes: &Mutex<QuoteManager>, i: &Interaction) {
+    let Some(press) = i.as_message_component() else {
+        return;
+    };
+    let Some(id) = press
+        .data
+        .custom_id
+        .strip_prefix(UPVOTE_BUTTON)
+        .and_then(|id| id.parse().ok())
+    else {
+        return;
+    };
+    let row = {
+        let mut quotes = quotes.lock().await;
+        match quotes.vote(id, press.user.id).await {
+            Ok(Some(_)) => vote_button(id, &quotes.0[id]),
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("failed to save vote for quote #{id}: {e:?}");
+                return;
+            }
+        }
+    };
+    if let Err(e) = press
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new().components(vec![row]),
+            ),
+        )
+        .await
+    {
+        tracing::error!("failed to update quote votes: {e:?}");
+    }
+}
+
+pub async fn initialize(
+    d: &Marc<Mutex<DaemonManager>>,
+    quotes: &Marc<Mutex<QuoteManager>>,
+    events: &pubsub::EventBus,
+) -> io::Result<()> {
+    d.lock()
+        .await
+        .add_daemon(QuoteOfTheDayPoster {
+            quotes: quotes.clone(),
+        })
+        .await;
+    let quotes = quotes.clone();
+    events
+        .subscribe::<events::InteractionCreate, _>(move |ctx, i| {
+            let quotes = quotes.clone();
+            async move {
+                handle_vote(&ctx.serenity, &quotes, i).await;
+                ControlFlow::Continue(())
+            }
+            .boxed()
+        })
+        .await;
+    Ok(())
+}
+
+struct QuoteOfTheDayPoster {
+    quotes: Marc<Mutex<QuoteManager>>,
+}
+
+impl QuoteOfTheDayPoster {
+    async fn post(
+        &self,
+        http: &Http,
+        gid: GuildId,
+        config: QuoteOfTheDay,
+        rotation: &mut Rotation,
+    ) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let today = (now + Duration::hours(config.utc_offset.into())).naive_utc();
+        if today.time() < config.time
+            || rotation.last_posted == Some(today.date())
+            || rotation.retry_at.is_some_and(|at| at > now)
+        {
+            return Ok(());
+        }
+        let (id, quote, row) = {
+            let quotes = self.quotes.lock().await;
+            let Some(id) = rotation.upcoming(quotes.0.len()) else {
+                return Ok(());
+            };
+            let quote = &quotes.0[id];
+            (id, quote.text.clone(), vote_button(id, quote))
+        };
+        tracing::info!(%gid, id, "posting quote of the day");
+        config
+            .channel
+            .send_message(
+                http,
+                CreateMessage::new()
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Quote of the day")
+                            .description(quote)
+                            .footer(CreateEmbedFooter::new(format!("#{id}"))),
+                    )
+                    .components(vec![row]),
+            )
+            .await?;
+        rotation.posted(today.date());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Daemon<true> for QuoteOfTheDayPoster {
+    type Data = (Arc<serenity::cache::Cache>, Arc<Http>);
+
+    async fn run(&mut self, data: &Self::Data) -> daemons::ControlFlow {
+        let guilds = match guild_prefs::all().await {
+            Ok(g) => g,
+            Err(e) => {
+                tracing::error!("failed to load guild prefs: {e:?}");
+                return ControlFlow::Continue(());
+            }
+        };
+        let mut rotations = match ROTATIONS.load().await {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::error!("failed to load quote rotations: {e:?}");
+                return ControlFlow::Continue(());
+            }
+        };
+        for (gid, config) in guilds
+            .into_iter()
+            .filter_map(|(gid, p)| Some((gid, p.quote_of_the_day?)))
+        {
+            let rotation = rotations.entry(gid).or_default();
+            if let Err(e) = self.post(&data.1, gid, config, rotation).await {
+                tracing::error!("failed to post quote of the day in {gid}: {e:?}");
+                if rotation.failed(Utc::now()) {
+                    tracing::warn!(%gid, "disabling quote of the day after repeated failures");
+                    rotation.failures = 0;
+                    rotation.retry_at = None;
+                    if let Err(e) = guild_prefs::update(gid, |p| p.quote_of_the_day = None).await {
+                        tracing::error!("failed to disable quote of the day in {gid}: {e:?}");
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    async fn interval(&self) -> StdDuration {
+        StdDuration::from_secs(60)
+    }
+
+    async fn name(&self) -> String {
+        stringify!(QuoteOfTheDayPoster).to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MAX_FAILURES, Quote, QuoteManager, Rotation};
+    use chrono::{Duration, Utc};
+    use std::collections::HashSet;
+
+    #[test]
+    fn deser_plain_and_voted_quotes() {
+        let s = r#"["old quote",{"text":"new quote","voters":["98500250540478464"]}]"#;
+        let quotes: QuoteManager = serde_json::from_str(s).unwrap();
+        assert_eq!(quotes.get(0).unwrap().text, "old quote");
+        assert_eq!(quotes.get(0).unwrap().score(), 0);
+        assert_eq!(quotes.get(1).unwrap().score(), 1);
+    }
+
+    #[tokio::test]
+    async fn load_falls_back_to_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quotes.json");
+        std::fs::write(&path, "[\"truncat").unwrap();
+        std::fs::write(QuoteManager::backup_path(&path, 1), r#"["backed up"]"#).unwrap();
+        let quotes = QuoteManager::load_from(&path).await.unwrap();
+        assert_eq!(quotes.get(0).unwrap().text, "backed up");
+    }
+
+    #[tokio::test]
+    async fn load_moves_unreadable_quotes_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quotes.json");
+        std::fs::write(&path, "[\"truncat").unwrap();
+        let quotes = QuoteManager::load_from(&path).await.unwrap();
+        assert_eq!(quotes.iter().count(), 0);
+        assert!(!path.exists());
+        assert!(dir.path().join("quotes.json.corrupt").exists());
+    }
+
+    #[tokio::test]
+    async fn backups_rotate_at_most_once_per_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quotes.json");
+        let mut quotes = QuoteManager::default();
+        for quote in ["first", "second", "third"] {
+            quotes.0.push(Quote {
+                text: quote.into(),
+                voters: Default::default(),
+            });
+            quotes.save_to(&path).await.unwrap();
+        }
+        let backup = QuoteManager::read(&QuoteManager::backup_path(&path, 1))
             .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(backup.iter().count(), 1);
+        assert!(!QuoteManager::backup_path(&path, 2).exists());
+        let quotes = QuoteManager::load_from(&path).await.unwrap();
+        assert_eq!(quotes.iter().count(), 3);
+    }
+
+    #[test]
+    fn rotation_cycles_through_every_quote_before_repeating() {
+        let mut rotation = Rotation::default();
+        let today = Utc::now().date_naive();
+        let seen = (0..5)
+            .map(|_| {
+                let id = rotation.upcoming(5).unwrap();
+                rotation.posted(today);
+                id
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(seen.len(), 5);
+        assert!(rotation.remaining.is_empty());
+        assert!(rotation.upcoming(5).is_some());
+    }
+
+    #[test]
+    fn rotation_keeps_quote_until_posted() {
+        let mut rotation = Rotation::default();
+        let id = rotation.upcoming(5);
+        assert!(!rotation.failed(Utc::now()));
+        assert_eq!(rotation.upcoming(5), id);
+        assert_eq!(rotation.remaining.len(), 5);
+    }
+
+    #[test]
+    fn rotation_gives_up_after_repeated_failures() {
+        let mut rotation = Rotation::default();
+        let now = Utc::now();
+        assert!(!rotation.failed(now));
+        assert_eq!(rotation.retry_at, Some(now + Duration::minutes(2)));
+        assert!((2..MAX_FAILURES).all(|_| !rotation.failed(now)));
+        assert!(rotation.failed(now));
+    }
+
+    #[test]
+    fn rotation_of_empty_collection() {
+        assert_eq!(Rotation::default().upcoming(0), None);
     }
 }
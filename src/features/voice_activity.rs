@@ -0,0 +1,195 @@
+use crate::in_files;
+use chrono::{DateTime, Duration, Utc};
+use futures::FutureExt as _;
+use json_db::GlobalDatabase;
+use pubsub::events;
+use serde::{Deserialize, Serialize};
+use serenity::all::{Cache, ChannelId, GuildId, UserId, VoiceState};
+use std::{
+    collections::HashMap,
+    io,
+    ops::ControlFlow,
+    sync::{LazyLock, Mutex},
+};
+
+/// Sessions shorter than this are just people passing through, they aren't recorded.
+const MIN_SESSION: Duration = Duration::minutes(1);
+
+static SESSIONS: GlobalDatabase<HashMap<GuildId, Vec<Session>>> =
+    GlobalDatabase::new(in_files!("voice_sessions.json"));
+
+/// When each member in a voice channel joined it.
+static ONGOING: LazyLock<Mutex<HashMap<(GuildId, UserId), (ChannelId, DateTime<Utc>)>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub user: UserId,
+    pub channel: ChannelId,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// The guild's sessions since `since`, including the ones still going on.
+pub async fn sessions(guild_id: GuildId, since: Option<DateTime<Utc>>) -> io::Result<Vec<Session>> {
+    let mut sessions = SESSIONS
+        .load()
+        .await?
+        .get(&guild_id)
+        .cloned()
+        .unwrap_or_default();
+    let now = Utc::now();
+    sessions.extend(
+        ONGOING
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((g, _), _)| *g == guild_id)
+            .map(|((_, user), (channel, start))| Session {
+                user: *user,
+                channel: *channel,
+                start: *start,
+                end: now,
+            }),
+    );
+    if let Some(since) = since {
+        sessions.retain(|s| s.end >= since);
+        // only the part of a session inside the period counts
+        for s in &mut sessions {
+            s.start = s.start.max(since);
+        }
+    }
+    Ok(sessions)
+}
+
+/// How long each member spent in voice, longest first.
+pub fn totals(sessions: &[Session]) -> Vec<(UserId, Duration)> {
+    let mut totals = HashMap::<UserId, Duration>::new();
+    for s in sessions {
+        *totals.entry(s.user).or_insert_with(Duration::zero) += s.duration();
+    }
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_unstable_by(|(a_id, a), (b_id, b)| b.cmp(a).then_with(|| a_id.cmp(b_id)));
+    totals
+}
+
+fn is_afk_channel(cache: &Cache, guild_id: GuildId, channel: ChannelId) -> bool {
+    cache
+        .guild(guild_id)
+        .and_then(|g| g.afk_metadata.as_ref().map(|m| m.afk_channel_id))
+        == Some(channel)
+}
+
+async fn end(guild_id: GuildId, user: UserId) -> io::Result<()> {
+    let Some((channel, start)) = ONGOING.lock().unwrap().remove(&(guild_id, user)) else {
+        return Ok(());
+    };
+    let session = Session {
+        user,
+        channel,
+        start,
+        end: Utc::now(),
+    };
+    if session.duration() >= MIN_SESSION {
+        SESSIONS
+            .load()
+            .await?
+            .entry(guild_id)
+            .or_default()
+            .push(session);
+    }
+    Ok(())
+}
+
+fn start(guild_id: GuildId, user: UserId, channel: ChannelId) {
+    ONGOING
+        .lock()
+        .unwrap()
+        .entry((guild_id, user))
+        .or_insert((channel, Utc::now()));
+}
+
+async fn track(cache: &Cache, old: Option<&VoiceState>, new: &VoiceState) -> io::Result<()> {
+    let Some(guild_id) = new.guild_id else {
+        return Ok(());
+    };
+    if new.member.as_ref().is_some_and(|m| m.user.bot) {
+        return Ok(());
+    }
+    let (old, new_channel) = (old.and_then(|vs| vs.channel_id), new.channel_id);
+    if old == new_channel {
+        return Ok(());
+    }
+    end(guild_id, new.user_id).await?;
+    if let Some(channel) = new_channel
+        && !is_afk_channel(cache, guild_id, channel)
+    {
+        start(guild_id, new.user_id, channel);
+    }
+    Ok(())
+}
+
+pub async fn initialize(events: &pubsub::EventBus) {
+    events
+        .subscribe::<events::VoiceStateUpdate, _>(|ctx, events::VoiceStateUpdate { old, new }| {
+            async move {
+                if let Err(e) = track(&ctx.serenity.cache, old.as_ref(), new).await {
+                    tracing::error!("Failed to record voice activity: {e:?}");
+                }
+                ControlFlow::Continue(())
+            }
+            .boxed()
+        })
+        .await;
+    // whoever was already in voice when the bot started
+    events
+        .subscribe::<events::GuildCreate, _>(|_, events::GuildCreate { guild, .. }| {
+            async move {
+                let afk_channel = guild.afk_metadata.as_ref().map(|m| m.afk_channel_id);
+                for vs in guild.voice_states.values() {
+                    if let Some(channel) = vs.channel_id
+                        && Some(channel) != afk_channel
+                        && !vs.member.as_ref().is_some_and(|m| m.user.bot)
+                    {
+                        start(guild.id, vs.user_id, channel);
+                    }
+                }
+                ControlFlow::Continue(())
+            }
+            .boxed()
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session(user: u64, minutes: i64) -> Session {
+        let start = Utc::now();
+        Session {
+            user: UserId::new(user),
+            channel: ChannelId::new(1),
+            start,
+            end: start + Duration::minutes(minutes),
+        }
+    }
+
+    #[test]
+    fn totals_add_up_sessions_longest_first() {
+        let sessions = [session(1, 10), session(2, 30), session(1, 25)];
+        assert_eq!(
+            totals(&sessions),
+            [
+                (UserId::new(1), Duration::minutes(35)),
+                (UserId::new(2), Duration::minutes(30)),
+            ]
+        );
+    }
+}
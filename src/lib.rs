@@ -66,8 +66,11 @@ impl Bot {
         features::moderation::reaction_roles::initialize(&EVENT_BUS)
             .await
             .context("initializing reaction roles")?;
+        commands::sfx::initialize(&mut daemon_manager, &EVENT_BUS).await;
+        commands::tts::initialize(&EVENT_BUS).await;
         features::music_channel_broadcast::initialize(&EVENT_BUS).await;
         features::disconnect_channel::initialize(&EVENT_BUS).await;
+        features::voice_activity::initialize(&EVENT_BUS).await;
 
         let this = Marc::new(Bot {
             daemons: Mutex::new(daemon_manager),
@@ -84,6 +87,8 @@ impl Bot {
             try_init!(features::birthdays, daemons);
             try_init!(features::mtg_spoilers, daemons, EVENT_BUS);
             try_init!(features::mc, daemons);
+            let quotes = Marc::map(this.clone(), |b| &b.quotes);
+            try_init!(features::quotes, daemons, quotes, EVENT_BUS);
         }
 
         Ok(this)
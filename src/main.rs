@@ -191,6 +191,7 @@ async fn post_init_bot(
             commands.extend(command_groups::quotes().chain([
                 command_groups::sfx(),
                 command_groups::tts(),
+                command_groups::voice(),
                 command_groups::bday(),
             ]));
         }
@@ -233,6 +234,14 @@ async fn on_dispatch_error(
             let _ = ctx.say(error.to_string()).await;
             tracing::error!(cmd = ?ctx.command().name, ?error, "Command failed");
         }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            let _ = ctx.say(error.to_string()).await;
+            tracing::debug!(cmd = ?ctx.command().name, ?error, "Command check failed");
+        }
         error => {
             tracing::error!(?error, "framework error");
         }
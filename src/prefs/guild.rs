@@ -1,7 +1,11 @@
+use chrono::{Duration, NaiveTime};
 use json_db::GlobalDatabase;
 use serde::{Deserialize, Serialize};
 use serenity::model::id::{ChannelId, GuildId, RoleId};
-use std::{collections::HashMap, io};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+};
 
 use crate::in_files;
 
@@ -14,12 +18,124 @@ pub struct GuildPrefs {
     pub birthday_channel: Option<ChannelId>,
     #[serde(default)]
     pub birthday_role: Option<RoleId>,
+    #[serde(default)]
+    pub quote_of_the_day: Option<QuoteOfTheDay>,
+    #[serde(default)]
+    pub sfx_hide_shared_library: bool,
+    #[serde(default)]
+    pub sfx_mode: SfxMode,
+    #[serde(default)]
+    pub sfx_intros: bool,
+    #[serde(default)]
+    pub mod_log_channel: Option<ChannelId>,
+    #[serde(default)]
+    pub sfx_permissions: SfxPermissions,
+    #[serde(default)]
+    pub tts_engine: TtsEngine,
+    #[serde(default)]
+    pub tts_voice: Option<TtsVoice>,
+    /// Messages sent here are read out in their author's voice channel.
+    #[serde(default)]
+    pub tts_reader_channel: Option<ChannelId>,
+    /// Minutes the bot stays in a call with nothing playing, 0 to stay until everyone leaves.
+    #[serde(default)]
+    pub voice_idle_timeout: Option<u64>,
+    #[serde(default)]
+    pub disconnect_channel: Option<DisconnectChannel>,
+}
+
+impl GuildPrefs {
+    /// How long the bot stays in a call with nothing playing, if it ever leaves.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match self.voice_idle_timeout {
+            None => Some(Duration::minutes(30)),
+            Some(0) => None,
+            Some(minutes) => Duration::try_minutes(minutes.try_into().ok()?),
+        }
+    }
+}
+
+/// Roles that may do things with sfx. While a set is empty everyone may.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SfxPermissions {
+    #[serde(default)]
+    pub upload_roles: BTreeSet<RoleId>,
+    #[serde(default)]
+    pub delete_roles: BTreeSet<RoleId>,
+    #[serde(default)]
+    pub play_roles: BTreeSet<RoleId>,
+    /// Seconds members have to wait between playing sfx.
+    #[serde(default)]
+    pub play_cooldown: Option<u64>,
+}
+
+/// A voice channel that disconnects everyone who joins it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DisconnectChannel {
+    pub channel: ChannelId,
+    /// Members with any of these roles can stay in the channel.
+    #[serde(default)]
+    pub exempt_roles: BTreeSet<RoleId>,
+    /// Path of an sfx played before disconnecting everyone.
+    #[serde(default)]
+    pub sfx: Option<String>,
+}
+
+/// What happens when an sfx is played while another one is still playing.
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter,
+)]
+pub enum SfxMode {
+    /// Play it on top of the others.
+    #[default]
+    Overlap,
+    /// Wait for the others to finish.
+    Queue,
+}
+
+/// What turns tts messages into speech.
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter,
+)]
+pub enum TtsEngine {
+    /// Online services, through lazypy.ro.
+    #[default]
+    #[name = "lazypy.ro"]
+    Lazypy,
+    /// espeak-ng, running on the bot's machine.
+    #[name = "espeak-ng"]
+    Espeak,
+}
+
+/// A voice of one of the tts services.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TtsVoice {
+    pub service: String,
+    pub voice: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct QuoteOfTheDay {
+    pub channel: ChannelId,
+    /// Time of day, in the guild's local time, at which to post the quote.
+    pub time: NaiveTime,
+    /// Offset, in hours, from UTC of the guild's local time.
+    pub utc_offset: i8,
 }
 
 pub async fn get(u: GuildId) -> io::Result<Option<GuildPrefs>> {
     Ok(GUILD_PREFS.load().await?.get(&u).cloned())
 }
 
+pub async fn all() -> io::Result<Vec<(GuildId, GuildPrefs)>> {
+    Ok(GUILD_PREFS
+        .load()
+        .await?
+        .iter()
+        .map(|(g, p)| (*g, p.clone()))
+        .collect())
+}
+
 pub async fn update<F, R>(u: GuildId, mut f: F) -> io::Result<R>
 where
     F: FnMut(&mut GuildPrefs) -> R,
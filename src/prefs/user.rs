@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use serenity::model::id::UserId;
 use std::{collections::HashMap, io};
 
+use super::guild::TtsVoice;
 use crate::in_files;
 
 static USER_PREFS: GlobalDatabase<HashMap<UserId, UserPrefs>> =
@@ -12,6 +13,9 @@ static USER_PREFS: GlobalDatabase<HashMap<UserId, UserPrefs>> =
 pub struct UserPrefs {
     #[serde(default)]
     pub timezone_offset: Option<i8>,
+    /// Overrides the voice of the guild's tts.
+    #[serde(default)]
+    pub tts_voice: Option<TtsVoice>,
 }
 
 pub async fn get(u: UserId) -> io::Result<Option<UserPrefs>> {